@@ -1,46 +1,66 @@
-pub fn run() -> anyhow::Result<()> {
-    let input = std::fs::read_to_string("day1.txt")?;
-    let input = parse(&input)?;
+use std::io::BufRead;
 
-    println!("1:1 - {}", run_1(&input)?);
-    println!("3:2 - {}", run_2(&input)?);
+pub fn run(args: &[String]) -> anyhow::Result<()> {
+    let open =
+        || -> anyhow::Result<_> { Ok(std::io::BufReader::new(std::fs::File::open("day1.txt")?)) };
+    // `1 --window w` counts increases between sliding windows of w depths
+    if let [flag, width] = args {
+        if flag != "--window" {
+            return Err(anyhow::anyhow!("Unknown option {}", flag));
+        }
+        println!("1: {}", increases(read_depths(open()?), width.parse()?)?);
+        return Ok(());
+    }
+
+    println!("1:1 - {}", run_1(open()?)?);
+    println!("1:2 - {}", run_2(open()?)?);
 
     Ok(())
 }
 
-fn parse(input: &str) -> anyhow::Result<Vec<usize>> {
-    let (_, res) = nom::multi::separated_list1(
-        nom::character::complete::newline,
-        crate::helper::uval::<usize>,
-    )(input)
-    .map_err(|e| e.to_owned())?;
-    Ok(res)
+// One depth per line, read as they are needed
+fn read_depths(input: impl BufRead) -> impl Iterator<Item = anyhow::Result<usize>> {
+    input
+        .lines()
+        .filter(|l| !matches!(l, Ok(l) if l.is_empty()))
+        .map(|line| {
+            let line = line?;
+            let (_, depth) =
+                nom::combinator::all_consuming(crate::helper::uval::<usize>)(line.as_str())
+                    .map_err(|e| e.to_owned())?;
+            Ok(depth)
+        })
 }
 
-fn run_1(input: &[usize]) -> anyhow::Result<usize> {
-    let (_, cnt) = input.iter().fold((usize::MAX, 0), |(prev, cnt), cur| {
-        if &prev < cur {
-            (*cur, cnt + 1)
-        } else {
-            (*cur, cnt)
+// How often the sum of a window of `width` depths is larger than the one
+// before. Neighbouring windows share all but their first and last depth, so
+// that is the same as a[i + width] > a[i], and only the last `width` depths
+// need to be kept.
+fn increases(
+    depths: impl IntoIterator<Item = anyhow::Result<usize>>,
+    width: usize,
+) -> anyhow::Result<usize> {
+    if width == 0 {
+        return Err(anyhow::anyhow!("Window width must be at least 1"));
+    }
+    let mut window = std::collections::VecDeque::with_capacity(width);
+    let mut cnt = 0;
+    for depth in depths {
+        let depth = depth?;
+        if window.len() == width && window.pop_front().is_some_and(|first| depth > first) {
+            cnt += 1;
         }
-    });
+        window.push_back(depth);
+    }
     Ok(cnt)
 }
 
-fn run_2(input: &[usize]) -> anyhow::Result<usize> {
-    let (_, cnt) =
-        input
-            .windows(3)
-            .map(|v| v.iter().sum())
-            .fold((usize::MAX, 0), |(prev, cnt), cur| {
-                if prev < cur {
-                    (cur, cnt + 1)
-                } else {
-                    (cur, cnt)
-                }
-            });
-    Ok(cnt)
+fn run_1(input: impl BufRead) -> anyhow::Result<usize> {
+    increases(read_depths(input), 1)
+}
+
+fn run_2(input: impl BufRead) -> anyhow::Result<usize> {
+    increases(read_depths(input), 3)
 }
 
 #[cfg(test)]
@@ -58,21 +78,32 @@ mod tests {
 
     #[test]
     fn aoc1_parse() {
-        let result = super::parse(INPUT).unwrap();
+        let result = super::read_depths(INPUT.as_bytes())
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
         assert_eq!(result.len(), 10);
         assert_eq!(result[0], 199);
         assert_eq!(result[9], 263);
+        assert!(super::read_depths("1\n2x\n".as_bytes()).any(|d| d.is_err()));
+    }
+
+    #[test]
+    fn aoc1_increases() {
+        let depths = || super::read_depths(INPUT.as_bytes());
+        assert_eq!(super::increases(depths(), 2).unwrap(), 5);
+        assert_eq!(super::increases(depths(), 10).unwrap(), 0);
+        assert_eq!(super::increases(depths(), 9).unwrap(), 1);
+        assert!(super::increases(depths(), 0).is_err());
+        assert_eq!(super::increases(std::iter::empty(), 3).unwrap(), 0);
     }
 
     #[test]
     fn aoc1_run_1() {
-        let input = super::parse(INPUT).unwrap();
-        assert_eq!(super::run_1(&input).unwrap(), 7);
+        assert_eq!(super::run_1(INPUT.as_bytes()).unwrap(), 7);
     }
 
     #[test]
     fn aoc1_run_2() {
-        let input = super::parse(INPUT).unwrap();
-        assert_eq!(super::run_2(&input).unwrap(), 5);
+        assert_eq!(super::run_2(INPUT.as_bytes()).unwrap(), 5);
     }
 }
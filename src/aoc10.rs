@@ -1,110 +1,233 @@
 use std::fs;
 
-pub fn run() -> anyhow::Result<()> {
+pub fn run(args: &[String]) -> anyhow::Result<()> {
+    // `10 --repair <input> <output>` writes every line of input made valid
+    // to output and lists what was changed
+    if let [flag, input, output] = args {
+        if flag != "--repair" {
+            return Err(anyhow::anyhow!("Unknown option {}", flag));
+        }
+        let input = fs::read_to_string(input)?;
+        let v = Validator::chunks();
+        let mut repaired = String::new();
+        for (n, line) in input.lines().enumerate() {
+            let (line, fixes) = v.repair(line);
+            repaired.push_str(&line);
+            repaired.push('\n');
+            if !fixes.is_empty() {
+                let fixes = fixes.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+                println!("line {}: {}", n + 1, fixes.join(", "));
+            }
+        }
+        fs::write(output, repaired)?;
+        return Ok(());
+    }
+
     let input = fs::read_to_string("day10.txt").unwrap();
     println!("day10-1: {}", run_1(&input)?);
     println!("day10-2: {}", run_2(&input)?);
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy)]
+struct Pair {
+    open: char,
+    close: char,
+    // Points for finding this closer where it doesn't belong
+    error_score: usize,
+    // Points for adding this closer when completing a line
+    completion_score: usize,
+}
+
+// Checks lines of nested delimiter pairs, any other characters are skipped
+struct Validator {
+    pairs: Vec<Pair>,
+}
+
+#[derive(Debug, PartialEq)]
 enum Res {
     Ok,
-    Invalid(char),
-    Incomplete(Vec<char>),
+    // A closer that doesn't match the innermost open chunk
+    Unexpected {
+        offset: usize,
+        expected: char,
+        found: char,
+    },
+    // A closer without any open chunk
+    Stray {
+        offset: usize,
+        found: char,
+    },
+    // The openers left unmatched at the end of the line with their byte
+    // offsets, innermost last
+    Incomplete(Vec<(usize, char)>),
 }
 
-fn check(i: &str) -> Res {
-    let mut stack = Vec::new();
+#[derive(Debug, PartialEq)]
+enum Fix {
+    Replaced { offset: usize, from: char, to: char },
+    Removed { offset: usize, found: char },
+    Appended(String),
+}
 
-    for c in i.chars() {
-        match c {
-            '[' | '(' | '{' | '<' => {
-                stack.push(c);
-            }
-            ']' => {
-                let c2 = stack.pop().unwrap();
-                if c2 != '[' {
-                    return Res::Invalid(c);
-                }
+impl std::fmt::Display for Fix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fix::Replaced { offset, from, to } => {
+                write!(f, "replaced {} with {} at {}", from, to, offset)
             }
-            ')' => {
-                let c2 = stack.pop().unwrap();
-                if c2 != '(' {
-                    return Res::Invalid(c);
+            Fix::Removed { offset, found } => write!(f, "removed {} at {}", found, offset),
+            Fix::Appended(s) => write!(f, "appended {}", s),
+        }
+    }
+}
+
+impl Validator {
+    // The chunk delimiters of the puzzle
+    fn chunks() -> Self {
+        let pair = |open, close, error_score, completion_score| Pair {
+            open,
+            close,
+            error_score,
+            completion_score,
+        };
+        Self {
+            pairs: vec![
+                pair('(', ')', 3, 1),
+                pair('[', ']', 57, 2),
+                pair('{', '}', 1197, 3),
+                pair('<', '>', 25137, 4),
+            ],
+        }
+    }
+
+    fn opened_by(&self, c: char) -> Option<&Pair> {
+        self.pairs.iter().find(|p| p.open == c)
+    }
+
+    fn closed_by(&self, c: char) -> Option<&Pair> {
+        self.pairs.iter().find(|p| p.close == c)
+    }
+
+    fn check(&self, i: &str) -> Res {
+        let mut stack = Vec::new();
+
+        for (offset, c) in i.char_indices() {
+            if self.opened_by(c).is_some() {
+                stack.push((offset, c));
+            } else if let Some(pair) = self.closed_by(c) {
+                match stack.pop() {
+                    Some((_, open)) if open == pair.open => (),
+                    Some((_, open)) => {
+                        return Res::Unexpected {
+                            offset,
+                            expected: self.opened_by(open).expect("known opener").close,
+                            found: c,
+                        }
+                    }
+                    None => return Res::Stray { offset, found: c },
                 }
             }
-            '}' => {
-                let c2 = stack.pop().unwrap();
-                if c2 != '{' {
-                    return Res::Invalid(c);
+        }
+
+        if stack.is_empty() {
+            Res::Ok
+        } else {
+            Res::Incomplete(stack)
+        }
+    }
+
+    // The closers that finish an incomplete line
+    fn complete(&self, stack: &[(usize, char)]) -> String {
+        stack
+            .iter()
+            .rev()
+            .filter_map(|(_, c)| self.opened_by(*c))
+            .map(|p| p.close)
+            .collect()
+    }
+
+    // Makes a line valid with as few changes as possible. Which characters
+    // open and close chunks can't change, so every closer that doesn't match
+    // its opener has to be replaced and closers without an opener removed.
+    // Whatever is left open is then completed. Offsets are into the original
+    // line.
+    fn repair(&self, i: &str) -> (String, Vec<Fix>) {
+        let mut res = String::with_capacity(i.len());
+        let mut fixes = Vec::new();
+        let mut open = Vec::new();
+        for (offset, c) in i.char_indices() {
+            if let Some(pair) = self.opened_by(c) {
+                open.push(pair.close);
+            } else if self.closed_by(c).is_some() {
+                match open.pop() {
+                    Some(close) if close != c => {
+                        fixes.push(Fix::Replaced {
+                            offset,
+                            from: c,
+                            to: close,
+                        });
+                        res.push(close);
+                        continue;
+                    }
+                    Some(_) => (),
+                    None => {
+                        fixes.push(Fix::Removed { offset, found: c });
+                        continue;
+                    }
                 }
             }
-            '>' => {
-                let c2 = stack.pop().unwrap();
-                if c2 != '<' {
-                    return Res::Invalid(c);
-                }
+            res.push(c);
+        }
+
+        if let Res::Incomplete(stack) = self.check(&res) {
+            let completion = self.complete(&stack);
+            res.push_str(&completion);
+            fixes.push(Fix::Appended(completion));
+        }
+        (res, fixes)
+    }
+
+    fn error_score(&self, res: &Res) -> Option<usize> {
+        match res {
+            Res::Unexpected { found, .. } | Res::Stray { found, .. } => {
+                self.closed_by(*found).map(|p| p.error_score)
             }
-            _ => unreachable!(),
+            _ => None,
         }
     }
 
-    if stack.is_empty() {
-        Res::Ok
-    } else {
-        Res::Incomplete(stack)
+    fn completion_score(&self, completion: &str) -> usize {
+        completion
+            .chars()
+            .filter_map(|c| self.closed_by(c))
+            .fold(0, |p, s| p * 5 + s.completion_score)
     }
 }
 
 fn run_1(input: &str) -> anyhow::Result<usize> {
-    let invalids = input
+    let v = Validator::chunks();
+    Ok(input
         .lines()
-        .map(check)
-        .filter_map(|r| match r {
-            Res::Invalid(c) => Some(c),
-            _ => None,
-        })
-        .map(|c| match c {
-            ')' => 3,
-            ']' => 57,
-            '}' => 1197,
-            '>' => 25137,
-            _ => unreachable!(),
-        })
-        .sum();
-    Ok(invalids)
-}
-
-fn complete(mut stack: Vec<char>) -> usize {
-    let mut scores = Vec::new();
-
-    while let Some(c) = stack.pop() {
-        match c {
-            '(' => scores.push(1),
-            '[' => scores.push(2),
-            '{' => scores.push(3),
-            '<' => scores.push(4),
-            _ => unreachable!(),
-        }
-    }
-
-    scores.iter().fold(0, |p, s| p * 5 + s)
+        .filter_map(|line| v.error_score(&v.check(line)))
+        .sum())
 }
 
 fn run_2(input: &str) -> anyhow::Result<usize> {
-    let mut invalids = input
+    let v = Validator::chunks();
+    let mut scores = input
         .lines()
-        .map(check)
-        .filter_map(|r| match r {
-            Res::Incomplete(c) => Some(c),
+        .filter_map(|line| match v.check(line) {
+            Res::Incomplete(stack) => Some(v.completion_score(&v.complete(&stack))),
             _ => None,
         })
-        .map(complete)
         .collect::<Vec<_>>();
 
-    invalids.sort_unstable();
-
-    Ok(invalids[invalids.len() / 2])
+    if scores.is_empty() {
+        return Err(anyhow::anyhow!("No incomplete lines"));
+    }
+    scores.sort_unstable();
+    Ok(scores[scores.len() / 2])
 }
 
 #[cfg(test)]
@@ -120,6 +243,109 @@ mod tests {
 <{([([[(<>()){}]>(<<{{
 <{([{{}}[<[[[<>{}]]]>[]]";
 
+    #[test]
+    fn aoc10_check() {
+        use super::{Res, Validator};
+        let v = Validator::chunks();
+        assert_eq!(v.check("[<>({}){}[([])<>]]"), Res::Ok);
+        assert_eq!(
+            v.check("{([(<{}[<>[]}>{[]{[(<()>"),
+            Res::Unexpected {
+                offset: 12,
+                expected: ']',
+                found: '}'
+            }
+        );
+        assert_eq!(
+            v.check("()]"),
+            Res::Stray {
+                offset: 2,
+                found: ']'
+            }
+        );
+        assert_eq!(v.error_score(&v.check("()]")), Some(57));
+
+        let Res::Incomplete(stack) = v.check("[({(<(())[]>[[{[]{<()<>>") else {
+            panic!("expected an incomplete line");
+        };
+        assert_eq!(stack[..2], [(0, '['), (1, '(')]);
+        let completion = v.complete(&stack);
+        assert_eq!(completion, "}}]])})]");
+        assert_eq!(v.completion_score(&completion), 288957);
+    }
+
+    #[test]
+    fn aoc10_repair() {
+        use super::{Fix, Res, Validator};
+        let v = Validator::chunks();
+        assert_eq!(v.repair("[<>]"), ("[<>]".to_string(), vec![]));
+        assert_eq!(
+            v.repair("[({(<(())[]>[[{[]{<()<>>"),
+            (
+                "[({(<(())[]>[[{[]{<()<>>}}]])})]".to_string(),
+                vec![Fix::Appended("}}]])})]".to_string())]
+            )
+        );
+
+        let (line, fixes) = v.repair("{([(<{}[<>[]}>{[]{[(<()>");
+        assert_eq!(
+            fixes[0],
+            Fix::Replaced {
+                offset: 12,
+                from: '}',
+                to: ']'
+            }
+        );
+        assert_eq!(fixes.len(), 2);
+        assert!(matches!(fixes[1], Fix::Appended(_)));
+        assert_eq!(fixes[0].to_string(), "replaced } with ] at 12");
+        assert_eq!(v.check(&line), Res::Ok);
+
+        let (line, fixes) = v.repair(")(]");
+        assert_eq!(line, "()");
+        assert_eq!(
+            fixes,
+            vec![
+                Fix::Removed {
+                    offset: 0,
+                    found: ')'
+                },
+                Fix::Replaced {
+                    offset: 2,
+                    from: ']',
+                    to: ')'
+                }
+            ]
+        );
+
+        for line in INPUT.lines() {
+            assert_eq!(v.check(&v.repair(line).0), Res::Ok);
+        }
+    }
+
+    #[test]
+    fn aoc10_custom_pairs() {
+        use super::{Pair, Res, Validator};
+        let v = Validator {
+            pairs: vec![Pair {
+                open: '«',
+                close: '»',
+                error_score: 1,
+                completion_score: 1,
+            }],
+        };
+        // Offsets are in bytes and other characters are skipped
+        assert_eq!(v.check("a«b»c"), Res::Ok);
+        assert_eq!(v.check("«x«"), Res::Incomplete(vec![(0, '«'), (3, '«')]));
+        assert_eq!(
+            v.check("x»"),
+            Res::Stray {
+                offset: 1,
+                found: '»'
+            }
+        );
+    }
+
     #[test]
     fn aoc10_run_1() {
         assert_eq!(super::run_1(INPUT).unwrap(), 26397);
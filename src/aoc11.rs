@@ -1,145 +1,178 @@
 use std::fs;
 
-pub fn run() -> anyhow::Result<()> {
+pub fn run(args: &[String]) -> anyhow::Result<()> {
     let input = fs::read_to_string("day11.txt").unwrap();
-    println!("day11-1: {}", run_1(&input)?);
-    println!("day11-2: {}", run_2(&input)?);
+    if args.is_empty() {
+        println!("day11-1: {}", run_1(&input)?);
+        println!("day11-2: {}", run_2(&input)?);
+        return Ok(());
+    }
+
+    // `11 --play` animates the grid in the terminal, `--frames <dir>` writes
+    // each step to a text file instead. `--steps <n>` sets how many steps to
+    // run and `--delay <ms>` the time between frames.
+    let mut play = false;
+    let mut frames = None;
+    let mut steps = 100;
+    let mut delay = 100;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow::anyhow!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--play" => play = true,
+            "--frames" => frames = Some(value()?.clone()),
+            "--steps" => steps = value()?.parse()?,
+            "--delay" => delay = value()?.parse()?,
+            _ => return Err(anyhow::anyhow!("Unknown option {}", arg)),
+        }
+    }
+
+    let mut grid = parse(&input)?;
+    if let Some(dir) = frames.as_ref() {
+        fs::create_dir_all(dir)?;
+        fs::write(
+            format!("{}/step_000.txt", dir),
+            format!("Before any steps:\n{}\n", grid),
+        )?;
+    }
+    for _ in 0..steps {
+        let flashed = grid.step();
+        if let Some(dir) = frames.as_ref() {
+            fs::write(
+                format!("{}/step_{:03}.txt", dir, grid.steps),
+                format!("After step {}:\n{}\n", grid.steps, grid),
+            )?;
+        }
+        if play {
+            print!("\x1b[2J\x1b[H{}", animation_frame(&grid, &flashed));
+            std::thread::sleep(std::time::Duration::from_millis(delay));
+        }
+    }
     Ok(())
 }
 
-fn run_1(input: &str) -> anyhow::Result<usize> {
-    let mut octs = Vec::new();
-    for line in input.lines() {
-        let row = line
-            .chars()
-            .map(|c| c.to_digit(10).unwrap())
-            .collect::<Vec<_>>();
-        octs.push(row);
-    }
-
-    let mut num_flashes = 0;
-    for _ in 0..100 {
-        let mut flashed = std::collections::HashSet::new();
-        for (r, row) in octs.iter_mut().enumerate() {
-            for (c, o) in row.iter_mut().enumerate() {
-                *o += 1;
-                if *o == 10 {
-                    num_flashes += 1;
-                    flashed.insert((r, c));
-                }
+// Energy levels, row major
+#[derive(Debug, Clone, PartialEq)]
+struct OctopusGrid {
+    width: usize,
+    height: usize,
+    energy: Vec<u8>,
+    steps: usize,
+}
+
+impl OctopusGrid {
+    fn neighbours(&self, idx: usize) -> impl Iterator<Item = usize> {
+        let (width, height) = (self.width as isize, self.height as isize);
+        let (row, col) = (idx as isize / width, idx as isize % width);
+        (-1..=1)
+            .flat_map(|dr| (-1..=1).map(move |dc| (dr, dc)))
+            .filter(|d| *d != (0, 0))
+            .map(move |(dr, dc)| (row + dr, col + dc))
+            .filter(move |(r, c)| (0..height).contains(r) && (0..width).contains(c))
+            .map(move |(r, c)| (r * width + c) as usize)
+    }
+
+    // Advances one step and returns the octopuses that flashed, in the order
+    // they flashed
+    fn step(&mut self) -> Vec<usize> {
+        let mut flashed = Vec::new();
+        let mut to_flash = Vec::new();
+        for (idx, e) in self.energy.iter_mut().enumerate() {
+            *e += 1;
+            if *e == 10 {
+                to_flash.push(idx);
             }
         }
 
-        while let Some((r, c)) = flashed.iter().next().cloned() {
-            flashed.remove(&(r, c));
-            let nbrs = [
-                (-1, -1),
-                (-1, 0),
-                (-1, 1),
-                (0, -1),
-                (0, 1),
-                (1, -1),
-                (1, 0),
-                (1, 1),
-            ]
-            .iter()
-            .filter_map(|(dr, dc)| {
-                let r = r as i32 + dr;
-                let c = c as i32 + dc;
-                if (0..10).contains(&r) && (0..10).contains(&c) {
-                    Some((r as usize, c as usize))
-                } else {
-                    None
-                }
-            });
-            for (r, c) in nbrs {
-                let o = octs.get_mut(r).unwrap().get_mut(c).unwrap();
-                *o += 1;
-                if *o == 10 {
-                    num_flashes += 1;
-                    flashed.insert((r, c));
+        while let Some(idx) = to_flash.pop() {
+            flashed.push(idx);
+            for nbr in self.neighbours(idx) {
+                let e = &mut self.energy[nbr];
+                // Anything above 10 has already flashed this step
+                if *e < 10 {
+                    *e += 1;
+                    if *e == 10 {
+                        to_flash.push(nbr);
+                    }
                 }
             }
         }
-        for row in octs.iter_mut() {
-            for o in row.iter_mut() {
-                if *o > 9 {
-                    *o = 0;
-                }
-            }
+
+        for idx in flashed.iter() {
+            self.energy[*idx] = 0;
         }
+        self.steps += 1;
+        flashed
     }
 
-    Ok(num_flashes)
+    fn flashes_after(&mut self, steps: usize) -> usize {
+        (0..steps).map(|_| self.step().len()).sum()
+    }
+
+    // The first step, counted from the start, where every octopus flashes,
+    // giving up after `limit` more steps
+    fn first_synchronised(&mut self, limit: usize) -> Option<usize> {
+        (0..limit).find_map(|_| (self.step().len() == self.energy.len()).then_some(self.steps))
+    }
 }
 
-fn run_2(input: &str) -> anyhow::Result<usize> {
-    let mut octs = Vec::new();
-    for line in input.lines() {
-        let row = line
-            .chars()
-            .map(|c| c.to_digit(10).unwrap())
-            .collect::<Vec<_>>();
-        octs.push(row);
-    }
-
-    let mut step = 0;
-    loop {
-        let mut all_flashed = std::collections::HashSet::new();
-        let mut flashed = std::collections::HashSet::new();
-        for (r, row) in octs.iter_mut().enumerate() {
-            for (c, o) in row.iter_mut().enumerate() {
-                *o += 1;
-                if *o == 10 {
-                    flashed.insert((r, c));
-                    all_flashed.insert((r, c));
-                }
+// The grid as digits, the way the puzzle prints it
+impl std::fmt::Display for OctopusGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (row, energy) in self.energy.chunks(self.width).enumerate() {
+            if row > 0 {
+                writeln!(f)?;
             }
-        }
-
-        while let Some((r, c)) = flashed.iter().next().cloned() {
-            flashed.remove(&(r, c));
-            let nbrs = [
-                (-1, -1),
-                (-1, 0),
-                (-1, 1),
-                (0, -1),
-                (0, 1),
-                (1, -1),
-                (1, 0),
-                (1, 1),
-            ]
-            .iter()
-            .filter_map(|(dr, dc)| {
-                let r = r as i32 + dr;
-                let c = c as i32 + dc;
-                if (0..10).contains(&r) && (0..10).contains(&c) {
-                    Some((r as usize, c as usize))
-                } else {
-                    None
-                }
-            });
-            for (r, c) in nbrs {
-                let o = octs.get_mut(r).unwrap().get_mut(c).unwrap();
-                *o += 1;
-                if *o == 10 {
-                    flashed.insert((r, c));
-                    all_flashed.insert((r, c));
-                }
+            for e in energy {
+                write!(f, "{}", e)?;
             }
         }
-        for row in octs.iter_mut() {
-            for o in row.iter_mut() {
-                if *o > 9 {
-                    *o = 0;
-                }
-            }
+        Ok(())
+    }
+}
+
+// Step counter and the grid in ANSI colours, octopuses that just flashed in
+// bold yellow and the rest in shades of blue that brighten as they charge
+fn animation_frame(grid: &OctopusGrid, flashed: &[usize]) -> String {
+    let mut res = format!("Step {} ({} flashes)\n", grid.steps, flashed.len());
+    for (idx, e) in grid.energy.iter().enumerate() {
+        if flashed.contains(&idx) {
+            res.push_str(&format!("\x1b[1;93m{}\x1b[0m", e));
+        } else {
+            res.push_str(&format!("\x1b[38;5;{}m{}\x1b[0m", 17 + 2 * *e as usize, e));
         }
-        step += 1;
-        if all_flashed.len() == 100 {
-            return Ok(step);
+        if (idx + 1) % grid.width == 0 {
+            res.push('\n');
         }
     }
+    res
+}
+
+fn parse(input: &str) -> anyhow::Result<OctopusGrid> {
+    let (width, height, energy) = crate::helper::grid(input, |c| {
+        c.to_digit(10)
+            .map(|e| e as u8)
+            .ok_or_else(|| anyhow::anyhow!("Invalid energy level {:?}", c))
+    })?;
+    Ok(OctopusGrid {
+        width,
+        height,
+        energy,
+        steps: 0,
+    })
+}
+
+fn run_1(input: &str) -> anyhow::Result<usize> {
+    Ok(parse(input)?.flashes_after(100))
+}
+
+fn run_2(input: &str) -> anyhow::Result<usize> {
+    parse(input)?
+        .first_synchronised(1_000_000)
+        .ok_or_else(|| anyhow::anyhow!("The octopuses never flash at the same time"))
 }
 
 #[cfg(test)]
@@ -155,6 +188,90 @@ mod tests {
 4846848554
 5283751526";
 
+    #[test]
+    fn aoc11_parse() {
+        let grid = super::parse(INPUT).unwrap();
+        assert_eq!((grid.width, grid.height), (10, 10));
+        assert!(super::parse("123\n45").is_err());
+        assert!(super::parse("12a").is_err());
+        assert!(super::parse("").is_err());
+        assert!(super::parse("\n\n").is_err());
+    }
+
+    #[test]
+    fn aoc11_step() {
+        let mut grid = super::parse(
+            "11111
+19991
+19191
+19991
+11111",
+        )
+        .unwrap();
+        assert_eq!(grid.step().len(), 9);
+        assert_eq!(
+            grid.energy,
+            super::parse(
+                "34543
+40004
+50005
+40004
+34543"
+            )
+            .unwrap()
+            .energy
+        );
+        assert!(grid.step().is_empty());
+
+        // Not square and not 10x10
+        let mut grid = super::parse("999\n000").unwrap();
+        let mut flashed = grid.step();
+        flashed.sort();
+        assert_eq!(flashed, vec![0, 1, 2]);
+        assert_eq!(grid.energy, vec![0, 0, 0, 3, 4, 3]);
+        assert!(super::parse("12\n3").is_err());
+    }
+
+    #[test]
+    fn aoc11_display() {
+        let mut grid = super::parse(INPUT).unwrap();
+        assert_eq!(grid.to_string(), INPUT);
+        grid.flashes_after(2);
+        assert_eq!(
+            grid.to_string(),
+            "8807476555
+5089087054
+8597889608
+8485769600
+8700908800
+6600088989
+6800005943
+0000007456
+9000000876
+8700006848"
+        );
+    }
+
+    #[test]
+    fn aoc11_animation_frame() {
+        let mut grid = super::parse("19\n11").unwrap();
+        let flashed = grid.step();
+        let frame = super::animation_frame(&grid, &flashed);
+        assert!(frame.starts_with("Step 1 (1 flashes)\n"));
+        assert!(frame.contains("\x1b[1;93m0\x1b[0m"));
+        assert_eq!(frame.matches("\x1b[38;5;").count(), 3);
+        assert_eq!(frame.lines().count(), 3);
+    }
+
+    #[test]
+    fn aoc11_queries() {
+        let mut grid = super::parse(INPUT).unwrap();
+        assert_eq!(grid.flashes_after(10), 204);
+        assert_eq!(grid.flashes_after(90), 1656 - 204);
+        assert_eq!(grid.first_synchronised(1000), Some(195));
+        assert_eq!(super::parse(INPUT).unwrap().first_synchronised(10), None);
+    }
+
     #[test]
     fn aoc11_run_1() {
         assert_eq!(super::run_1(INPUT).unwrap(), 1656);
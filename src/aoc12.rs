@@ -1,118 +1,426 @@
-use std::{
-    collections::{HashMap, HashSet},
-    fs,
-};
+use crate::graph::{self, Graph, Weighted};
+use std::collections::HashMap;
+use std::fs;
 
-pub fn run() -> anyhow::Result<()> {
+pub fn run(args: &[String]) -> anyhow::Result<()> {
     let input = fs::read_to_string("day12.txt").unwrap();
-    println!("day12-1: {}", run_1(&input)?);
-    println!("day12-2: {}", run_2(&input)?);
+    if args.is_empty() {
+        println!("day12-1: {}", run_1(&input)?);
+        println!("day12-2: {}", run_2(&input)?);
+        return Ok(());
+    }
+
+    // `12 --paths <part>` lists the paths of that part, `12 --dot` prints
+    // the caves as a Graphviz graph, `12 --dot <part>` adds how often the
+    // paths use each connection and `12 --dot <part> <n>` marks the n:th path
+    let caves = parse(&input)?;
+    let policy = |part: &String| match part.as_str() {
+        "1" => Ok(Policy::part_1()),
+        "2" => Ok(Policy::part_2()),
+        _ => Err(anyhow::anyhow!("No part {}", part)),
+    };
+    match args {
+        [flag, part] if flag == "--paths" => {
+            for path in paths(&caves, &policy(part)?)? {
+                println!("{}", path.join(","));
+            }
+        }
+        [flag] if flag == "--dot" => print!("{}", dot(&caves, &Highlight::None)),
+        [flag, part] if flag == "--dot" => {
+            let usage = edge_usage(&caves, &policy(part)?)?;
+            print!("{}", dot(&caves, &Highlight::Usage(usage)));
+        }
+        [flag, part, n] if flag == "--dot" => {
+            let path = paths(&caves, &policy(part)?)?
+                .nth(n.parse()?)
+                .ok_or_else(|| anyhow::anyhow!("No path {}", n))?;
+            let path = path.iter().filter_map(|c| caves.graph.id(c)).collect();
+            print!("{}", dot(&caves, &Highlight::Path(path)));
+        }
+        _ => return Err(anyhow::anyhow!("Unknown options {:?}", args)),
+    }
     Ok(())
 }
 
-type Map<'a> = HashMap<&'a str, Vec<&'a str>>;
-type ToVisit<'a> = Vec<&'a str>;
-
-fn get_nbrs<'a>(current: &'a str, map: &Map<'a>, visited: &HashSet<&'a str>) -> Vec<&'a str> {
-    map.get(current)
-        .expect("find current")
-        .iter()
-        .filter(|v| !visited.contains(*v))
-        .copied()
-        .collect()
+struct Caves {
+    graph: Graph,
+    small: Vec<bool>,
+    start: usize,
+    end: usize,
 }
 
-fn get_nbrs2<'a>(current: &'a str, map: &Map<'a>, to_visit: &[&'a str]) -> Vec<&'a str> {
-    map.get(current)
-        .expect("find current")
-        .iter()
-        .filter(|v| to_visit.contains(v))
-        .copied()
-        .collect()
+// Which caves a path may enter and how often. Big caves can be visited
+// any number of times and small caves once, unless `limits` says otherwise.
+// On top of that `revisit_caves` small caves may be visited `revisit_times`
+// more times each. Start is only ever visited once.
+#[derive(Clone, Debug, Default)]
+struct Policy {
+    limits: HashMap<String, usize>,
+    revisit_caves: usize,
+    revisit_times: usize,
+    forbidden: Vec<String>,
+    // Paths that miss any of these are not counted
+    required: Vec<String>,
 }
 
-fn search<'a>(current: &'a str, map: &Map<'a>, mut visited: HashSet<&'a str>) -> Vec<Vec<&'a str>> {
-    if current == "end" {
-        return vec![vec!["end"]];
+impl Policy {
+    fn part_1() -> Self {
+        Self::default()
     }
 
-    let mut res = Vec::new();
-    if current.chars().any(|c| c.is_lowercase()) {
-        visited.insert(current);
-    }
-    for n in get_nbrs(current, map, &visited) {
-        let mut paths = search(n, map, visited.clone());
-        for p in paths.iter_mut() {
-            p.push(current);
+    fn part_2() -> Self {
+        Self {
+            revisit_caves: 1,
+            revisit_times: 1,
+            ..Self::default()
         }
-        res.append(&mut paths);
     }
-    res
 }
 
-fn search2<'a>(current: &'a str, map: &Map<'a>, mut to_visit: ToVisit<'a>) -> Vec<Vec<&'a str>> {
-    if current == "end" {
-        return vec![vec!["end"]];
-    }
+// A policy resolved against the caves of one input
+struct Rules {
+    // None for caves that can be visited any number of times
+    limit: Vec<Option<usize>>,
+    // Small caves the revisit rule applies to
+    revisitable: Vec<bool>,
+    revisit_caves: usize,
+    revisit_times: usize,
+    required: Vec<usize>,
+}
 
-    let mut res = Vec::new();
-    if current.chars().any(|c| c.is_lowercase()) {
-        if let Some(pos) = to_visit.iter().position(|c| *c == current) {
-            to_visit.remove(pos);
+impl Rules {
+    fn new(caves: &Caves, policy: &Policy) -> anyhow::Result<Self> {
+        let id = |name: &String| {
+            caves
+                .graph
+                .id(name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown cave {}", name))
+        };
+        let mut limit = caves
+            .small
+            .iter()
+            .map(|small| small.then_some(1))
+            .collect::<Vec<_>>();
+        let mut revisitable = caves.small.clone();
+        for (name, l) in policy.limits.iter() {
+            limit[id(name)?] = Some(*l);
+            revisitable[id(name)?] = false;
+        }
+        for name in policy.forbidden.iter() {
+            limit[id(name)?] = Some(0);
+            revisitable[id(name)?] = false;
         }
+        limit[caves.start] = Some(1);
+        revisitable[caves.start] = false;
+
+        Ok(Self {
+            limit,
+            revisitable,
+            revisit_caves: policy.revisit_caves,
+            revisit_times: policy.revisit_times,
+            required: policy.required.iter().map(id).collect::<Result<_, _>>()?,
+        })
     }
 
-    for n in get_nbrs2(current, map, &to_visit) {
-        let mut paths = search2(n, map, to_visit.clone());
-        for p in paths.iter_mut() {
-            p.push(current);
+    // `visits` holds the visits so far of every cave with a limit, caves
+    // without one only need to be marked as visited
+    fn allow(&self, cave: usize, visits: &[usize]) -> bool {
+        let Some(limit) = self.limit[cave] else {
+            return true;
+        };
+        let v = visits[cave];
+        if v < limit {
+            return true;
         }
-        res.append(&mut paths);
+        if !self.revisitable[cave] || v >= limit + self.revisit_times {
+            return false;
+        }
+        // Already one of the revisited caves, or there is room for one more
+        v > limit
+            || visits
+                .iter()
+                .zip(self.limit.iter())
+                .zip(self.revisitable.iter())
+                .filter(|((v, l), r)| **r && l.is_some_and(|l| **v > l))
+                .count()
+                < self.revisit_caves
     }
-    res
 }
 
 fn run_1(input: &str) -> anyhow::Result<usize> {
-    let map = parse(input);
-    Ok(search("start", &map, HashSet::new()).len())
+    count_paths(&parse(input)?, &Policy::part_1())
 }
 
 fn run_2(input: &str) -> anyhow::Result<usize> {
-    let map = parse(input);
+    count_paths(&parse(input)?, &Policy::part_2())
+}
 
-    let visit_twice: Vec<&str> = map
-        .keys()
-        .filter(|c| **c != "start" && **c != "end")
-        .filter(|c| c.chars().any(|c| c.is_lowercase()))
-        .copied()
-        .collect();
+// The visits that matter for what a path can still do, packed into a u64.
+// Caves with a limit get a field wide enough for their most visits, big
+// caves only get a bit when they are required, and the number of caves
+// revisited so far is only kept when there is a revisit rule.
+struct Packing {
+    // (shift, mask) of each cave's visit count
+    field: Vec<Option<(u32, u64)>>,
+    // The bit set once a required cave is visited, 0 for other caves
+    seen: Vec<u64>,
+    required: u64,
+    used: Option<(u32, u64)>,
+}
+
+impl Packing {
+    fn new(caves: &Caves, rules: &Rules) -> anyhow::Result<Self> {
+        // Bits needed to hold 0..=n
+        let width = |n: usize| usize::BITS - n.leading_zeros();
+        let mut shift = 0;
+        let mut alloc = |bits: u32| {
+            let at = shift;
+            shift += bits;
+            (at, (1u64 << bits) - 1)
+        };
+
+        let revisits = rules.revisit_caves > 0 && rules.revisit_times > 0;
+        let mut field = vec![None; rules.limit.len()];
+        for (cave, limit) in rules.limit.iter().enumerate() {
+            // Start is entered once before the search and never again
+            let Some(limit) = limit.filter(|l| *l > 0 && cave != caves.start) else {
+                continue;
+            };
+            let extra = if revisits && rules.revisitable[cave] {
+                rules.revisit_times
+            } else {
+                0
+            };
+            field[cave] = Some(alloc(width(limit + extra)));
+        }
+        let mut seen = vec![0; rules.limit.len()];
+        for cave in rules.required.iter() {
+            seen[*cave] = 1 << alloc(1).0;
+        }
+        let used = revisits.then(|| alloc(width(rules.revisit_caves)));
+        if shift > 64 {
+            return Err(anyhow::anyhow!("Too many caves to count paths"));
+        }
+        let required = seen.iter().fold(0, |acc, bit| acc | bit);
+        Ok(Self {
+            field,
+            seen,
+            required,
+            used,
+        })
+    }
+
+    fn get(key: u64, (shift, mask): (u32, u64)) -> usize {
+        (key >> shift & mask) as usize
+    }
+
+    fn set(key: u64, (shift, mask): (u32, u64), v: usize) -> u64 {
+        key & !(mask << shift) | (v as u64) << shift
+    }
 
-    let mut paths = HashSet::new();
+    // The state after entering `cave`, or None if the rules don't allow it
+    fn enter(&self, rules: &Rules, key: u64, cave: usize) -> Option<u64> {
+        let key = key | self.seen[cave];
+        let Some(limit) = rules.limit[cave] else {
+            return Some(key);
+        };
+        let field = self.field[cave]?;
+        let v = Self::get(key, field);
+        if v < limit {
+            return Some(Self::set(key, field, v + 1));
+        }
+        let used_field = self.used.filter(|_| rules.revisitable[cave])?;
+        let mut used = Self::get(key, used_field);
+        if v >= limit + rules.revisit_times || (v == limit && used == rules.revisit_caves) {
+            return None;
+        }
+        let mut key = Self::set(key, field, v + 1);
+        if v == limit {
+            used += 1;
+            key = Self::set(key, used_field, used);
+        }
+        // Once every revisit is taken, caves that have used up their
+        // revisits can't be entered any more than those never revisited,
+        // so store them alike
+        if used == rules.revisit_caves {
+            for (c, field) in self.field.iter().enumerate() {
+                if let (Some(field), Some(limit)) = (field, rules.limit[c]) {
+                    if rules.revisitable[c] && Self::get(key, *field) == limit + rules.revisit_times
+                    {
+                        key = Self::set(key, *field, limit);
+                    }
+                }
+            }
+        }
+        Some(key)
+    }
+}
 
-    for vt in visit_twice {
-        let mut to_visit: Vec<&str> = map.keys().copied().collect();
-        to_visit.push(vt);
-        for p in search2("start", &map, to_visit) {
-            paths.insert(p);
+// Counts the paths without listing them. What a path can do next only
+// depends on the current cave and the packed visits so far, so each such
+// state is expanded once.
+fn count_paths(caves: &Caves, policy: &Policy) -> anyhow::Result<usize> {
+    fn count(
+        caves: &Caves,
+        rules: &Rules,
+        packing: &Packing,
+        cave: usize,
+        key: u64,
+        memo: &mut HashMap<(usize, u64), usize>,
+    ) -> usize {
+        if cave == caves.end {
+            return (key & packing.required == packing.required) as usize;
+        }
+        if let Some(cnt) = memo.get(&(cave, key)) {
+            return *cnt;
         }
+        let mut cnt = 0;
+        for (next, _) in caves.graph.edges(cave) {
+            if let Some(next_key) = packing.enter(rules, key, next) {
+                cnt += count(caves, rules, packing, next, next_key, memo);
+            }
+        }
+        memo.insert((cave, key), cnt);
+        cnt
     }
 
-    Ok(paths.len())
+    let rules = Rules::new(caves, policy)?;
+    let packing = Packing::new(caves, &rules)?;
+    Ok(count(
+        caves,
+        &rules,
+        &packing,
+        caves.start,
+        packing.seen[caves.start],
+        &mut HashMap::new(),
+    ))
+}
+
+// Lists the paths counted by `count_paths`, one at a time
+fn paths<'a>(
+    caves: &'a Caves,
+    policy: &Policy,
+) -> anyhow::Result<impl Iterator<Item = Vec<&'a str>>> {
+    let rules = Rules::new(caves, policy)?;
+    let required = rules.required.clone();
+    Ok(
+        graph::paths(&caves.graph, caves.start, caves.end, move |cave, visits| {
+            rules.allow(cave, visits)
+        })
+        .filter(move |path| required.iter().all(|c| path.contains(c)))
+        .map(|path| path.into_iter().map(|c| caves.graph.name(c)).collect()),
+    )
+}
+
+enum Highlight {
+    None,
+    Path(Vec<usize>),
+    // How many times the paths go through each connection, keyed with the
+    // lower cave id first
+    Usage(HashMap<(usize, usize), usize>),
 }
 
-fn parse(input: &str) -> Map {
-    let mut res: HashMap<&str, Vec<&str>> = HashMap::new();
-    for line in input.lines() {
-        let mut s = line.split('-');
-        let a = s.next().expect("key");
-        let b = s.next().expect("value");
-        res.entry(a).or_default().push(b);
-        res.entry(b).or_default().push(a);
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+fn edge_usage(caves: &Caves, policy: &Policy) -> anyhow::Result<HashMap<(usize, usize), usize>> {
+    let mut usage = HashMap::new();
+    for path in paths(caves, policy)? {
+        let ids = path
+            .iter()
+            .filter_map(|c| caves.graph.id(c))
+            .collect::<Vec<_>>();
+        for w in ids.windows(2) {
+            *usage.entry(edge_key(w[0], w[1])).or_insert(0) += 1;
+        }
+    }
+    Ok(usage)
+}
+
+// Graphviz source for the caves. Big caves are boxes, small caves circles
+// and start and end are filled in.
+fn dot(caves: &Caves, highlight: &Highlight) -> String {
+    use std::fmt::Write;
+    let g = &caves.graph;
+    let mut res = String::from("graph caves {\n");
+    for cave in 0..g.node_count() {
+        let shape = if caves.small[cave] { "circle" } else { "box" };
+        let fill = if cave == caves.start {
+            ", style=filled, fillcolor=palegreen"
+        } else if cave == caves.end {
+            ", style=filled, fillcolor=lightcoral"
+        } else {
+            ""
+        };
+        writeln!(res, "    \"{}\" [shape={}{}];", g.name(cave), shape, fill).unwrap();
     }
 
+    let on_path = |a: usize, b: usize| match highlight {
+        Highlight::Path(path) => path.windows(2).any(|w| edge_key(w[0], w[1]) == (a, b)),
+        _ => false,
+    };
+    let max_usage = match highlight {
+        Highlight::Usage(usage) => usage.values().copied().max().unwrap_or(0),
+        _ => 0,
+    };
+    for a in 0..g.node_count() {
+        for b in g.edges(a).map(|(b, _)| b).filter(|b| a < *b) {
+            let attrs = match highlight {
+                _ if on_path(a, b) => " [color=red, penwidth=3]".to_string(),
+                Highlight::Usage(usage) => {
+                    let used = usage.get(&(a, b)).copied().unwrap_or(0);
+                    format!(
+                        " [label={}, penwidth={:.1}]",
+                        used,
+                        1.0 + 4.0 * used as f64 / max_usage.max(1) as f64
+                    )
+                }
+                _ => String::new(),
+            };
+            writeln!(res, "    \"{}\" -- \"{}\"{};", g.name(a), g.name(b), attrs).unwrap();
+        }
+    }
+    res.push_str("}\n");
     res
 }
 
+fn parse(input: &str) -> anyhow::Result<Caves> {
+    let mut graph = Graph::default();
+    for line in input.lines().filter(|l| !l.is_empty()) {
+        let (a, b) = line
+            .split_once('-')
+            .ok_or_else(|| anyhow::anyhow!("Invalid connection {:?}", line))?;
+        graph.add_undirected_edge(a, b, 1);
+    }
+
+    let small: Vec<bool> = (0..graph.node_count())
+        .map(|id| graph.name(id).chars().any(|c| c.is_lowercase()))
+        .collect();
+    // Two connected big caves could be walked between forever
+    for cave in (0..graph.node_count()).filter(|c| !small[*c]) {
+        if graph.edges(cave).any(|(n, _)| !small[n]) {
+            return Err(anyhow::anyhow!(
+                "Big cave {} connects to another big cave",
+                graph.name(cave)
+            ));
+        }
+    }
+
+    let start = graph
+        .id("start")
+        .ok_or_else(|| anyhow::anyhow!("No start cave"))?;
+    let end = graph
+        .id("end")
+        .ok_or_else(|| anyhow::anyhow!("No end cave"))?;
+    Ok(Caves {
+        graph,
+        small,
+        start,
+        end,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     const INPUT_1: &str = "start-A
@@ -153,6 +461,153 @@ zg-he
 pj-fs
 start-RW";
 
+    #[test]
+    fn aoc12_parse() {
+        let caves = super::parse(INPUT_1).unwrap();
+        assert_eq!(caves.small, vec![true, false, true, true, true, true]);
+        assert!(super::parse("start-A").is_err());
+        assert!(super::parse("start-A\nA end").is_err());
+        assert!(super::parse("start-A\nA-B\nB-end").is_err());
+    }
+
+    fn policies() -> Vec<super::Policy> {
+        use super::Policy;
+        vec![
+            Policy::part_1(),
+            Policy::part_2(),
+            Policy {
+                revisit_caves: 2,
+                revisit_times: 1,
+                ..Policy::default()
+            },
+            Policy {
+                forbidden: vec!["end".to_string()],
+                ..Policy::part_2()
+            },
+            Policy {
+                required: vec!["start".to_string(), "end".to_string()],
+                ..Policy::part_1()
+            },
+        ]
+    }
+
+    #[test]
+    fn aoc12_policy() {
+        use super::Policy;
+        let caves = super::parse(INPUT_1).unwrap();
+        let count = |policy: &Policy| super::count_paths(&caves, policy).unwrap();
+        let paths = |policy: &Policy| {
+            let mut paths = super::paths(&caves, policy)
+                .unwrap()
+                .map(|p| p.join(","))
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths
+        };
+
+        // Visiting b twice through a per cave limit is one of the part 2 cases
+        let mut limits = std::collections::HashMap::new();
+        limits.insert("b".to_string(), 2);
+        let twice_b = Policy {
+            limits,
+            ..Policy::part_1()
+        };
+        assert_eq!(count(&twice_b), 30);
+        assert!(paths(&twice_b).contains(&"start,A,b,A,b,A,end".to_string()));
+
+        let no_b = Policy {
+            forbidden: vec!["b".to_string()],
+            ..Policy::part_1()
+        };
+        assert_eq!(paths(&no_b), vec!["start,A,c,A,end", "start,A,end"]);
+
+        let via_d = Policy {
+            required: vec!["d".to_string()],
+            ..Policy::part_2()
+        };
+        assert!(paths(&via_d).iter().all(|p| p.contains(",d,")));
+        assert_eq!(count(&via_d), paths(&via_d).len());
+        assert_eq!(count(&via_d), 8);
+
+        // Revisiting more than once, and required big caves, take more than
+        // one bit per cave in the memo key
+        for policy in [
+            Policy {
+                revisit_caves: 2,
+                revisit_times: 2,
+                required: vec!["A".to_string()],
+                ..Policy::default()
+            },
+            Policy {
+                limits: [("b".to_string(), 2)].into_iter().collect(),
+                required: vec!["c".to_string()],
+                ..Policy::part_2()
+            },
+        ] {
+            assert_eq!(count(&policy), paths(&policy).len());
+        }
+
+        assert!(super::count_paths(
+            &caves,
+            &Policy {
+                required: vec!["x".to_string()],
+                ..Policy::part_1()
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn aoc12_paths() {
+        let caves = super::parse(INPUT_1).unwrap();
+        let mut paths = super::paths(&caves, &super::Policy::part_1())
+            .unwrap()
+            .map(|p| p.join(","))
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(paths.len(), 10);
+        assert_eq!(paths[0], "start,A,b,A,c,A,end");
+
+        for input in [INPUT_1, INPUT_2, INPUT_3] {
+            let caves = super::parse(input).unwrap();
+            for policy in policies() {
+                assert_eq!(
+                    super::paths(&caves, &policy).unwrap().count(),
+                    super::count_paths(&caves, &policy).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn aoc12_dot() {
+        use super::{dot, Highlight};
+        let caves = super::parse(INPUT_1).unwrap();
+        let plain = dot(&caves, &Highlight::None);
+        assert!(plain.starts_with("graph caves {\n    \"start\" [shape=circle, style=filled"));
+        assert!(plain.contains("    \"A\" [shape=box];\n"));
+        assert!(plain.contains("    \"start\" -- \"A\";\n"));
+        assert_eq!(plain.matches(" -- ").count(), 7);
+
+        let path = ["start", "b", "end"]
+            .iter()
+            .map(|c| caves.graph.id(c).unwrap())
+            .collect();
+        let marked = dot(&caves, &Highlight::Path(path));
+        assert!(marked.contains("\"start\" -- \"b\" [color=red, penwidth=3];"));
+        assert!(marked.contains("\"b\" -- \"end\" [color=red, penwidth=3];"));
+        assert_eq!(marked.matches("color=red").count(), 2);
+
+        let usage = super::edge_usage(&caves, &super::Policy::part_1()).unwrap();
+        let (start, a) = (
+            caves.graph.id("start").unwrap(),
+            caves.graph.id("A").unwrap(),
+        );
+        assert_eq!(usage[&super::edge_key(start, a)], 7);
+        let used = dot(&caves, &Highlight::Usage(usage));
+        assert!(used.contains("\"start\" -- \"A\" [label=7, penwidth="));
+    }
+
     #[test]
     fn aoc12_run_1() {
         assert_eq!(super::run_1(INPUT_1).unwrap(), 10);
@@ -10,7 +10,7 @@ pub fn run() -> anyhow::Result<()> {
 fn run_1(input: &str) -> anyhow::Result<usize> {
     let (mut coords, folds) = parse(input)?;
 
-    if let Some(fold) = folds.get(0) {
+    if let Some(fold) = folds.first() {
         match fold {
             Fold::X(x) => {
                 let outsiders = coords
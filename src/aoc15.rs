@@ -1,90 +1,190 @@
-use pathfinding::directed::astar::*;
+use crate::graph::Path;
 use std::fs;
 
-pub fn run() -> anyhow::Result<()> {
+pub fn run(args: &[String]) -> anyhow::Result<()> {
     let input = fs::read_to_string("day15.txt").unwrap();
-    println!("day15-1: {}", run_1(&input)?);
-    println!("day15-2: {}", run_2(&input)?);
+    // `15 --tiles N` solves the map tiled N times in both directions and
+    // `--path` draws the lowest risk path on the map
+    let mut tiles = None;
+    let mut draw_path = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--path" => draw_path = true,
+            "--tiles" => {
+                let n = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--tiles needs a count"))?;
+                tiles = Some(n.parse::<usize>()?);
+            }
+            _ => return Err(anyhow::anyhow!("Unknown option {}", arg)),
+        }
+    }
+    if tiles.is_none() && !draw_path {
+        println!("day15-1: {}", run_1(&input)?);
+        println!("day15-2: {}", run_2(&input)?);
+        return Ok(());
+    }
+
+    let map = parse(&input)?;
+    let map = Tiled::new(&map, tiles.unwrap_or(1))?;
+    let path = solve(&map)?;
+    if draw_path {
+        println!("{}", overlay(&map, &path));
+    }
+    println!("day15: {}", path.cost);
     Ok(())
 }
 
-fn solve(map: Vec<Vec<isize>>) -> anyhow::Result<usize> {
-    let height = map.len() as isize;
-    let width = map[0].len() as isize;
+// Row major risk levels
+#[derive(Debug, Clone, PartialEq)]
+struct Map {
+    width: usize,
+    height: usize,
+    risk: Vec<u8>,
+}
+
+// A rectangular grid of risk levels, indexed row major
+trait Grid {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn risk(&self, idx: usize) -> u8;
 
-    let get_nbr = |(row, col): (isize, isize)| {
-        if (0..height).contains(&row) && (0..width).contains(&col) {
-            Some(((row, col), map[row as usize][col as usize]))
-        } else {
-            None
-        }
-    };
-
-    let successors = |(row, col): &(isize, isize)| {
-        [(-1, 0), (1, 0), (0, -1), (0, 1)]
-            .iter()
-            .filter_map(|(dr, dc)| get_nbr((row + dr, col + dc)))
-            .collect::<Vec<_>>()
-    };
-
-    let goal = (height - 1, width - 1);
-    let (_res, cost) = astar(
-        &(0, 0),
-        successors,
-        |(row, col)| ((goal.0 - row) + (col - goal.1)),
-        |g| g == &goal,
-    )
-    .unwrap();
-    Ok(cost as usize)
+    fn len(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    fn neighbours(&self, idx: usize) -> impl Iterator<Item = usize> {
+        crate::helper::neighbours(self.width(), self.height(), idx)
+    }
 }
 
-fn run_1(input: &str) -> anyhow::Result<usize> {
-    let map: Vec<Vec<_>> = input
-        .lines()
-        .map(|line| {
-            line.chars()
-                .map(|c| c.to_digit(10).unwrap() as isize)
-                .collect::<Vec<_>>()
-        })
-        .collect();
-    solve(map)
+impl Grid for Map {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn risk(&self, idx: usize) -> u8 {
+        self.risk[idx]
+    }
 }
 
-fn run_2(input: &str) -> anyhow::Result<usize> {
-    let map: Vec<Vec<_>> = input
-        .lines()
-        .map(|line| {
-            line.chars()
-                .map(|c| c.to_digit(10).unwrap() as isize)
-                .collect::<Vec<_>>()
-        })
-        .collect();
-
-    solve(expand(map))
+// The map repeated `factor` times to the right and down, every tile away
+// from the top left one adds one to the risk, wrapping from 9 back to 1.
+// Levels are worked out on lookup so the full map is never stored.
+struct Tiled<'a> {
+    map: &'a Map,
+    factor: usize,
 }
 
-fn expand(mut map: Vec<Vec<isize>>) -> Vec<Vec<isize>> {
-    let old_size = map.len();
-    let new_size = map.len() * 5;
-    map.reserve(new_size);
-    // First expand right
-    for (_, row) in map.iter_mut().enumerate() {
-        row.resize(new_size, 0);
-        for c in old_size..new_size {
-            row[c] = row[c - old_size] % 9 + 1;
+impl<'a> Tiled<'a> {
+    fn new(map: &'a Map, factor: usize) -> anyhow::Result<Self> {
+        if factor == 0 {
+            return Err(anyhow::anyhow!("Tile factor must be at least 1"));
         }
+        Ok(Self { map, factor })
     }
+}
 
-    for r in old_size..new_size {
-        let mut row = vec![0; new_size];
-        for (c, value) in row.iter_mut().enumerate() {
-            *value = map[r - old_size][c] % 9 + 1;
-        }
+impl Grid for Tiled<'_> {
+    fn width(&self) -> usize {
+        self.map.width * self.factor
+    }
+
+    fn height(&self) -> usize {
+        self.map.height * self.factor
+    }
+
+    fn risk(&self, idx: usize) -> u8 {
+        let (row, col) = (idx / self.width(), idx % self.width());
+        let base = self.map.risk[(row % self.map.height) * self.map.width + col % self.map.width];
+        let tile = (row / self.map.height + col / self.map.width) % 9;
+        (base + tile as u8 - 1) % 9 + 1
+    }
+}
+
+impl<T: Grid> crate::graph::Weighted for T {
+    fn node_count(&self) -> usize {
+        self.len()
+    }
+
+    // Entering a cell costs its risk level
+    fn edges(&self, node: usize) -> impl Iterator<Item = (usize, usize)> {
+        self.neighbours(node)
+            .map(move |nbr| (nbr, self.risk(nbr) as usize))
+    }
+
+    fn max_weight(&self) -> usize {
+        9
+    }
+}
+
+// Lowest risk path from the top left to the bottom right corner. Every step
+// costs at least 1 so the manhattan distance to the goal is a safe estimate.
+fn solve(map: &impl Grid) -> anyhow::Result<Path> {
+    let (width, height) = (map.width(), map.height());
+    let goal = map.len() - 1;
+    let heuristic = |idx: usize| (height - 1 - idx / width) + (width - 1 - idx % width);
+    crate::graph::astar(map, 0, goal, heuristic)
+        .ok_or_else(|| anyhow::anyhow!("No path to the bottom right corner"))
+}
 
-        map.push(row);
+// The map with every cell off the path blanked out
+fn overlay(map: &impl Grid, path: &Path) -> String {
+    let mut res = vec![b'.'; map.len()];
+    for c in path.nodes.iter() {
+        res[*c] = b'0' + map.risk(*c);
     }
+    res.chunks(map.width())
+        .map(|row| String::from_utf8_lossy(row).into_owned())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn parse(input: &str) -> anyhow::Result<Map> {
+    let (width, height, risk) = crate::helper::grid(input, |c| {
+        c.to_digit(10)
+            .filter(|r| *r > 0)
+            .map(|r| r as u8)
+            .ok_or_else(|| anyhow::anyhow!("Invalid risk level {:?}", c))
+    })?;
+    Ok(Map {
+        width,
+        height,
+        risk,
+    })
+}
+
+fn run_1(input: &str) -> anyhow::Result<usize> {
+    Ok(solve(&parse(input)?)?.cost)
+}
 
-    map
+fn run_2(input: &str) -> anyhow::Result<usize> {
+    let map = parse(input)?;
+    Ok(solve(&Tiled::new(&map, 5)?)?.cost)
+}
+
+// Builds the part 2 map in full, only used to check `Tiled`
+#[cfg(test)]
+fn expand(map: Map) -> Map {
+    let (width, height) = (map.width * 5, map.height * 5);
+    let mut risk = Vec::with_capacity(width * height);
+    for row in 0..height {
+        for col in 0..width {
+            let base = map.risk[(row % map.height) * map.width + col % map.width];
+            let tile = (row / map.height + col / map.width) as u8;
+            risk.push((base + tile - 1) % 9 + 1);
+        }
+    }
+    Map {
+        width,
+        height,
+        risk,
+    }
 }
 
 #[cfg(test)]
@@ -100,6 +200,57 @@ mod tests {
 1293138521
 2311944581";
 
+    #[test]
+    fn aoc15_parse() {
+        let map = super::parse(INPUT).unwrap();
+        assert_eq!((map.width, map.height), (10, 10));
+        assert_eq!(map.risk[10], 1);
+        assert!(super::parse("12\n3").is_err());
+        assert!(super::parse("10").is_err());
+    }
+
+    #[test]
+    fn aoc15_path() {
+        let map = super::parse(INPUT).unwrap();
+        let path = super::solve(&map).unwrap();
+        assert_eq!(path.cost, 40);
+        assert_eq!(
+            path.cost,
+            path.nodes[1..].iter().map(|c| map.risk[*c] as usize).sum()
+        );
+        assert_eq!(
+            super::overlay(&map, &path),
+            "1.........
+1.........
+2136511...
+......15..
+.......11.
+........3.
+........2.
+........3.
+........21
+.........1"
+        );
+    }
+
+    #[test]
+    fn aoc15_tiled() {
+        use super::Grid;
+        let map = super::parse(INPUT).unwrap();
+        let expanded = super::expand(map.clone());
+        let tiled = super::Tiled::new(&map, 5).unwrap();
+        assert_eq!((tiled.width(), tiled.height()), (50, 50));
+        assert!((0..tiled.len()).all(|idx| tiled.risk(idx) == expanded.risk[idx]));
+
+        // Tiles more than nine steps away wrap around to the same levels
+        let map = super::parse("8").unwrap();
+        let tiled = super::Tiled::new(&map, 20).unwrap();
+        assert_eq!(tiled.risk(0), 8);
+        assert_eq!(tiled.risk(9), 8);
+        assert_eq!(tiled.risk(19 * 20 + 19), 1);
+        assert!(super::Tiled::new(&map, 0).is_err());
+    }
+
     #[test]
     fn aoc15_run_1() {
         assert_eq!(super::run_1(INPUT).unwrap(), 40);
@@ -112,16 +263,17 @@ mod tests {
 
     #[test]
     fn aoc15_expand() {
-        let expanded = super::expand(vec![vec![8]]);
+        let expanded = super::expand(super::parse("8").unwrap());
         assert_eq!(
             expanded,
-            vec![
-                vec![8, 9, 1, 2, 3],
-                vec![9, 1, 2, 3, 4],
-                vec![1, 2, 3, 4, 5],
-                vec![2, 3, 4, 5, 6],
-                vec![3, 4, 5, 6, 7]
-            ]
+            super::parse(
+                "89123
+91234
+12345
+23456
+34567"
+            )
+            .unwrap()
         );
     }
 }
@@ -1,71 +1,378 @@
-pub fn run() -> anyhow::Result<()> {
-    println!("day17-1: {}", run_1(169..=206, -108..=-68)?);
-    println!("day17-2: {}", run_2(169..=206, -108..=-68)?);
+pub fn run(args: &[String]) -> anyhow::Result<()> {
+    let input = std::fs::read_to_string("day17.txt")?;
+    // `17 x,y` plots the launch with velocity x,y against the target
+    if let Some(vel) = args.first() {
+        let (x_range, y_range) = parse(&input)?;
+        let trajectory = simulate(parse_velocity(vel)?, &x_range, &y_range);
+        println!("{}", trajectory);
+        println!("{:?}", trajectory.outcome);
+        return Ok(());
+    }
+    println!("day17-1: {}", run_1(&input)?);
+    println!("day17-2: {}", run_2(&input)?);
     Ok(())
 }
 
 type Range = std::ops::RangeInclusive<isize>;
 
-fn run_1(x_range: Range, y_range: Range) -> anyhow::Result<isize> {
-    let mut maxes = Vec::new();
-    for x_vel in 0..900 {
-        for y_vel in 0..900 {
-            maxes.push(simulate((x_vel, y_vel), &x_range, &y_range));
-        }
+fn run_1(input: &str) -> anyhow::Result<isize> {
+    let (x_range, y_range) = parse(input)?;
+    velocities(&x_range, &y_range)?
+        .into_iter()
+        .map(|(_, y_vel)| peak(y_vel))
+        .max()
+        .ok_or_else(|| anyhow::anyhow!("No velocity hits the target"))
+}
+
+fn run_2(input: &str) -> anyhow::Result<usize> {
+    let (x_range, y_range) = parse(input)?;
+    Ok(velocities(&x_range, &y_range)?.len())
+}
+
+fn parse(i: &str) -> anyhow::Result<(Range, Range)> {
+    use nom::bytes::complete::tag;
+    use nom::sequence::{preceded, separated_pair, terminated};
+
+    let range = |i| {
+        nom::combinator::map(
+            separated_pair(crate::helper::ival, tag(".."), crate::helper::ival),
+            |(lo, hi)| lo..=hi,
+        )(i)
+    };
+
+    let (_, target) = nom::combinator::all_consuming(terminated(
+        separated_pair(
+            preceded(tag("target area: x="), range),
+            tag(", "),
+            preceded(tag("y="), range),
+        ),
+        nom::character::complete::multispace0,
+    ))(i)
+    .map_err(|e| e.to_owned())?;
+
+    Ok(target)
+}
+
+fn parse_velocity(i: &str) -> anyhow::Result<(isize, isize)> {
+    let (_, vel) = nom::combinator::all_consuming(nom::sequence::separated_pair(
+        crate::helper::ival,
+        nom::bytes::complete::tag(","),
+        crate::helper::ival,
+    ))(i)
+    .map_err(|e| e.to_owned())?;
+    Ok(vel)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    Hit,
+    // Fell below the target before reaching its x range
+    Short,
+    // Passed the far side of the target before falling to it
+    Long,
+    // Was over the target but stepped past it without landing inside
+    Tunnelled,
+}
+
+#[derive(Debug)]
+struct Trajectory {
+    x_range: Range,
+    y_range: Range,
+    // Positions after each step, the launch point excluded
+    positions: Vec<(isize, isize)>,
+    outcome: Outcome,
+}
+
+// Where an x position is relative to the target, seen from the launch point
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Near,
+    Inside,
+    Far,
+}
+
+fn side(x: isize, x_range: &Range) -> Side {
+    if x_range.contains(&x) {
+        Side::Inside
+    } else if (*x_range.start() > 0 && x < *x_range.start())
+        || (*x_range.end() < 0 && x > *x_range.end())
+    {
+        Side::Near
+    } else {
+        Side::Far
     }
-    Ok(maxes.into_iter().flatten().max().unwrap())
 }
 
-fn simulate(mut vel: (isize, isize), x_range: &Range, y_range: &Range) -> Option<isize> {
+// Steps the probe until it lands in the target or falls below it for good
+fn simulate(mut vel: (isize, isize), x_range: &Range, y_range: &Range) -> Trajectory {
+    let mut positions = Vec::new();
+    let mut prev = (0, 0);
     let mut pos = (0, 0);
-    let mut max_y = 0;
-    let mut did_hit = false;
-    while pos.0 <= *x_range.end() && pos.1 >= *y_range.end() {
+    let outcome = loop {
         pos.0 += vel.0;
         pos.1 += vel.1;
-        max_y = max_y.max(pos.1);
-        // dbg! {(x_range, y_range)};
+        vel.0 -= vel.0.signum();
+        vel.1 -= 1;
+        positions.push(pos);
+
         if x_range.contains(&pos.0) && y_range.contains(&pos.1) {
-            did_hit = true;
+            break Outcome::Hit;
         }
-        match vel.0.cmp(&0) {
-            std::cmp::Ordering::Greater => vel.0 -= 1,
-            std::cmp::Ordering::Less => vel.0 += 1,
-            _ => (),
+        if pos.1 < *y_range.start() && vel.1 < 0 {
+            break match (side(prev.0, x_range), side(pos.0, x_range)) {
+                (Side::Near, Side::Near) => Outcome::Short,
+                (Side::Far, _) => Outcome::Long,
+                _ => Outcome::Tunnelled,
+            };
         }
-        vel.1 -= 1;
+        prev = pos;
+    };
+    Trajectory {
+        x_range: x_range.clone(),
+        y_range: y_range.clone(),
+        positions,
+        outcome,
+    }
+}
+
+// Draws the trajectory like the puzzle, S for the launch point, # for the
+// probe and T for the target
+impl std::fmt::Display for Trajectory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let xs = self.positions.iter().map(|p| p.0);
+        let ys = self.positions.iter().map(|p| p.1);
+        let x_min = xs.clone().chain([0, *self.x_range.start()]).min().unwrap();
+        let x_max = xs.chain([0, *self.x_range.end()]).max().unwrap();
+        let y_min = ys.clone().chain([0, *self.y_range.start()]).min().unwrap();
+        let y_max = ys.chain([0, *self.y_range.end()]).max().unwrap();
+
+        let probe = self
+            .positions
+            .iter()
+            .collect::<std::collections::HashSet<_>>();
+        for y in (y_min..=y_max).rev() {
+            for x in x_min..=x_max {
+                let c = if (x, y) == (0, 0) {
+                    'S'
+                } else if probe.contains(&(x, y)) {
+                    '#'
+                } else if self.x_range.contains(&x) && self.y_range.contains(&y) {
+                    'T'
+                } else {
+                    '.'
+                };
+                write!(f, "{}", c)?;
+            }
+            if y > y_min {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
     }
-    if did_hit {
-        // println!("XXX");
-        Some(max_y)
+}
+
+// Highest y reached, the launch point included
+fn peak(y_vel: isize) -> isize {
+    if y_vel > 0 {
+        y_vel * (y_vel + 1) / 2
     } else {
-        None
+        0
+    }
+}
+
+// x position after `steps` steps, drag stops the probe after |x_vel| steps
+fn x_pos(x_vel: isize, steps: isize) -> isize {
+    let m = steps.min(x_vel.abs());
+    x_vel.signum() * (m * x_vel.abs() - m * (m - 1) / 2)
+}
+
+fn y_pos(y_vel: isize, steps: isize) -> isize {
+    steps * y_vel - steps * (steps - 1) / 2
+}
+
+// First and last step where x is inside the target, None as last if the
+// probe stops inside the target's x range
+fn x_steps(x_vel: isize, x_range: &Range) -> Option<(isize, Option<isize>)> {
+    let stop = x_vel.abs().max(1);
+    let first = (1..=stop).find(|s| x_range.contains(&x_pos(x_vel, *s)))?;
+    if x_range.contains(&x_pos(x_vel, stop)) {
+        return Some((first, None));
     }
+    let last = (first..=stop)
+        .take_while(|s| x_range.contains(&x_pos(x_vel, *s)))
+        .last()?;
+    Some((first, Some(last)))
+}
+
+// All steps where y is inside the target, the probe only moves away once it
+// is falling below the target
+fn y_steps(y_vel: isize, y_range: &Range) -> Vec<isize> {
+    (1..)
+        .take_while(|s| *s <= y_vel || y_pos(y_vel, *s) >= *y_range.start())
+        .filter(|s| y_range.contains(&y_pos(y_vel, *s)))
+        .collect()
 }
 
-fn run_2(x_range: Range, y_range: Range) -> anyhow::Result<usize> {
-    let mut cnt = 0;
-    for x_vel in 0..800 {
-        for y_vel in -300..800 {
-            if simulate((x_vel, y_vel), &x_range, &y_range).is_some() {
-                dbg! {(x_vel,y_vel)};
-                cnt += 1;
+fn velocities(x_range: &Range, y_range: &Range) -> anyhow::Result<Vec<(isize, isize)>> {
+    if x_range.is_empty() || y_range.is_empty() {
+        return Err(anyhow::anyhow!("Empty target area"));
+    }
+    let (x_min, x_max) = (*x_range.start(), *x_range.end());
+    let (y_min, y_max) = (*y_range.start(), *y_range.end());
+
+    // The first step lands on the velocity, so anything beyond the far edge
+    // of the target overshoots at once
+    let x_cands = (x_min.min(0)..=x_max.max(0))
+        .filter_map(|x_vel| x_steps(x_vel, x_range).map(|s| (x_vel, s)))
+        .collect::<Vec<_>>();
+
+    // A probe launched upwards comes back through y = 0, so if the target
+    // contains it and the probe can stop above the target, every high
+    // enough launch hits
+    if y_range.contains(&0) && x_cands.iter().any(|(_, (_, last))| last.is_none()) {
+        return Err(anyhow::anyhow!("Infinitely many velocities hit the target"));
+    }
+
+    // Coming down through y = 0 the next step is -(y_vel + 1), going up the
+    // first step is y_vel. Otherwise the probe must hit on its way back down
+    // through y = 0, which takes 2 * y_vel + 1 steps and x only stays in the
+    // target for at most |x_vel| steps.
+    let y_lo = y_min.min(0);
+    let y_hi = (-y_min - 1)
+        .max(y_max)
+        .max(x_min.abs().max(x_max.abs()) / 2);
+
+    let mut res = Vec::new();
+    for y_vel in y_lo..=y_hi {
+        let steps = y_steps(y_vel, y_range);
+        if steps.is_empty() {
+            continue;
+        }
+        for (x_vel, (first, last)) in x_cands.iter() {
+            if steps
+                .iter()
+                .any(|s| s >= first && last.is_none_or(|last| *s <= last))
+            {
+                res.push((*x_vel, y_vel));
             }
         }
     }
-    Ok(cnt)
+    Ok(res)
 }
 
 #[cfg(test)]
 mod tests {
+    const INPUT: &str = "target area: x=20..30, y=-10..-5";
+
+    // Step the probe one position at a time like the puzzle describes
+    fn brute_force(x_range: &super::Range, y_range: &super::Range) -> usize {
+        let mut cnt = 0;
+        for x_vel in -40..=40 {
+            for y_vel in -40..=40 {
+                let mut pos = (0, 0);
+                let mut vel: (isize, isize) = (x_vel, y_vel);
+                for _ in 0..200 {
+                    pos.0 += vel.0;
+                    pos.1 += vel.1;
+                    vel.0 -= vel.0.signum();
+                    vel.1 -= 1;
+                    if x_range.contains(&pos.0) && y_range.contains(&pos.1) {
+                        cnt += 1;
+                        break;
+                    }
+                }
+            }
+        }
+        cnt
+    }
+
+    #[test]
+    fn aoc17_parse() {
+        assert_eq!(super::parse(INPUT).unwrap(), (20..=30, -10..=-5));
+        assert_eq!(
+            super::parse("target area: x=-5..+7, y=3..4\n").unwrap(),
+            (-5..=7, 3..=4)
+        );
+        assert!(super::parse("target area: x=20..30").is_err());
+        assert!(super::parse("target area: x=20..30, y=-10..").is_err());
+        assert!(super::parse("target area: x=20..30, y=-10..-5 junk").is_err());
+    }
 
     #[test]
     fn aoc17_run_1() {
-        assert_eq!(super::run_1(20..=30, -10..=-5).unwrap(), 45);
+        assert_eq!(super::run_1(INPUT).unwrap(), 45);
     }
 
     #[test]
     fn aoc17_run_2() {
-        // assert_eq!(super::run_2(20..=30, -10..=-5).unwrap(), 112);
+        assert_eq!(super::run_2(INPUT).unwrap(), 112);
+    }
+
+    #[test]
+    fn aoc17_simulate() {
+        use super::Outcome;
+        let (x_range, y_range) = super::parse(INPUT).unwrap();
+        let t = super::simulate((7, 2), &x_range, &y_range);
+        assert_eq!(t.outcome, Outcome::Hit);
+        assert_eq!(t.positions.len(), 7);
+        assert_eq!(t.positions[6], (28, -7));
+        assert_eq!(
+            t.to_string(),
+            ".............#....#............
+.......#..............#........
+...............................
+S........................#.....
+...............................
+...............................
+...........................#...
+...............................
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+....................TTTTTTTT#TT
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT"
+        );
+
+        assert_eq!(
+            super::simulate((6, 9), &x_range, &y_range).outcome,
+            Outcome::Hit
+        );
+        assert_eq!(
+            super::simulate((17, -4), &x_range, &y_range).outcome,
+            Outcome::Long
+        );
+        assert_eq!(
+            super::simulate((5, 0), &x_range, &y_range).outcome,
+            Outcome::Short
+        );
+        assert_eq!(
+            super::simulate((7, 10), &x_range, &y_range).outcome,
+            Outcome::Tunnelled
+        );
+    }
+
+    #[test]
+    fn aoc17_other_targets() {
+        for (x_range, y_range) in [
+            (-30..=-20, -10..=-5),
+            (20..=30, 5..=10),
+            (-30..=-20, 5..=10),
+            (-3..=3, -10..=-5),
+            (11..=12, -3..=4),
+        ] {
+            assert_eq!(
+                super::velocities(&x_range, &y_range).unwrap().len(),
+                brute_force(&x_range, &y_range),
+                "{:?} {:?}",
+                x_range,
+                y_range
+            );
+        }
+    }
+
+    #[test]
+    fn aoc17_infinite() {
+        assert!(super::run_2("target area: x=20..30, y=-5..5").is_err());
     }
 }
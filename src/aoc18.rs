@@ -86,6 +86,7 @@ fn parse(i: Input) -> PResult {
 }
 
 // fn find_leftmost(s: &mut SnailFish) -> Option<&mut jj>
+#[cfg(test)]
 fn explode(s: &mut SnailFish) {
     fn explode_inner(s: &mut SnailFish, d: usize) -> Option<SnailFish> {
         fn is_pair(s: &SnailFish) -> bool {
@@ -158,11 +159,13 @@ fn explode(s: &mut SnailFish) {
     explode_inner(s, 1);
 }
 
+#[cfg(test)]
 fn split(v: usize) -> SnailFish {
     let e = v % 2;
     SnailFish::Pair(Box::new((SnailFish::Num(v / 2), SnailFish::Num(v / 2 + e))))
 }
 
+#[cfg(test)]
 fn magnitude(s: &SnailFish) -> usize {
     match s {
         SnailFish::Num(s) => *s,
@@ -209,9 +212,9 @@ mod tests {
         assert_eq!(a, b);
 
         let (_, mut a) = super::parse("[[3,[2,[1,[7,3]]]],[6,[5,[4,[3,2]]]]]").unwrap();
-        let (_, b) = super::parse("[[3,[2,[8,0]]],[9,[5,[4,[3,2]]]]]").unwrap();
         super::explode(&mut a);
-        // assert_eq!(a, b);
+        // Should be [[3,[2,[8,0]]],[9,[5,[4,[3,2]]]]], which explode doesn't
+        // get right yet
     }
 
     #[test]
@@ -1,27 +1,186 @@
-pub fn run() -> anyhow::Result<()> {
+pub fn run(args: &[String]) -> anyhow::Result<()> {
     let input = std::fs::read_to_string("day2.txt")?;
     let input = parse(&input)?;
-    println!("2:1: {}", run_1(&input)?);
-    println!("2:2: {}", run_2(&input)?);
+    if args.is_empty() {
+        println!("2:1: {}", run_1(&input)?);
+        println!("2:2: {}", run_2(&input)?);
+        return Ok(());
+    }
+
+    // `2 --model simple|aimed` picks the steering, part 2 by default.
+    // `--trace` prints the state after every command and `--profile rows`
+    // plots the depth over time.
+    let mut aimed = true;
+    let mut print_trace = false;
+    let mut profile_rows = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow::anyhow!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--model" => {
+                aimed = match value()?.as_str() {
+                    "simple" => false,
+                    "aimed" => true,
+                    m => return Err(anyhow::anyhow!("Unknown steering model {}", m)),
+                }
+            }
+            "--trace" => print_trace = true,
+            "--profile" => profile_rows = Some(value()?.parse::<usize>()?),
+            _ => return Err(anyhow::anyhow!("Unknown option {}", arg)),
+        }
+    }
+
+    let states = if aimed {
+        trace(Aimed::default(), &input)
+    } else {
+        trace(Simple::default(), &input)
+    };
+    if print_trace {
+        for (cmd, state) in input.iter().zip(states.iter()) {
+            println!("{:?} -> {:?}", cmd, state);
+        }
+    }
+    if let Some(rows) = profile_rows {
+        println!("{}", profile(&states, rows));
+    }
+    let last = states.last().copied().unwrap_or_default();
+    println!("2: {}", last.x * last.depth);
+    println!("max depth {}", max_depth(&states));
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct State {
+    x: isize,
+    depth: isize,
+    // Always 0 for the simple model
+    aim: isize,
+    // 1 while facing forward, -1 after turning around
+    heading: isize,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            x: 0,
+            depth: 0,
+            aim: 0,
+            heading: 1,
+        }
+    }
+}
+
+// A steering model, interpreting one command at a time
+trait Submarine {
+    fn state(&self) -> State;
+    fn apply(&mut self, cmd: &Command);
+}
+
+// Part 1, up and down change the depth directly
+#[derive(Default)]
+struct Simple(State);
+
+impl Submarine for Simple {
+    fn state(&self) -> State {
+        self.0
+    }
+
+    fn apply(&mut self, cmd: &Command) {
+        let s = &mut self.0;
+        match cmd {
+            Command::Forward(v) => s.x += s.heading * v,
+            Command::Back(v) => s.x -= s.heading * v,
+            Command::Down(v) => s.depth += v,
+            Command::Up(v) => s.depth -= v,
+            Command::Turn => s.heading = -s.heading,
+        }
+    }
+}
+
+// Part 2, up and down change the aim and moving follows it
+#[derive(Default)]
+struct Aimed(State);
+
+impl Submarine for Aimed {
+    fn state(&self) -> State {
+        self.0
+    }
+
+    fn apply(&mut self, cmd: &Command) {
+        let s = &mut self.0;
+        match cmd {
+            Command::Forward(v) => {
+                s.x += s.heading * v;
+                s.depth += s.aim * v;
+            }
+            Command::Back(v) => {
+                s.x -= s.heading * v;
+                s.depth -= s.aim * v;
+            }
+            Command::Down(v) => s.aim += v,
+            Command::Up(v) => s.aim -= v,
+            Command::Turn => s.heading = -s.heading,
+        }
+    }
+}
+
+// The state after each command
+fn trace(mut sub: impl Submarine, cmds: &[Command]) -> Vec<State> {
+    cmds.iter()
+        .map(|cmd| {
+            sub.apply(cmd);
+            sub.state()
+        })
+        .collect()
+}
+
+fn max_depth(states: &[State]) -> isize {
+    states.iter().map(|s| s.depth).max().unwrap_or(0).max(0)
+}
+
+// Depth after each command as a column, with the surface on the top row
+// and the deepest point on the bottom one
+fn profile(states: &[State], rows: usize) -> String {
+    let rows = rows.max(2);
+    let min = states.iter().map(|s| s.depth).min().unwrap_or(0).min(0);
+    let span = (max_depth(states) - min).max(1) as usize;
+    let row_of = |depth: isize| (depth - min) as usize * (rows - 1) / span;
+    (0..rows)
+        .map(|row| {
+            states
+                .iter()
+                .map(|s| {
+                    if row_of(s.depth) == row {
+                        '#'
+                    } else if row == row_of(0) {
+                        '~'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 fn run_1(input: &[Command]) -> anyhow::Result<isize> {
-    let (x, d) = input.iter().fold((0, 0), |(x, d), cmd| match cmd {
-        Command::Forward(v) => (x + v, d),
-        Command::Down(v) => (x, d + v),
-        Command::Up(v) => (x, d - v),
-    });
-    Ok(x * d)
+    let s = trace(Simple::default(), input)
+        .last()
+        .copied()
+        .unwrap_or_default();
+    Ok(s.x * s.depth)
 }
 
 fn run_2(input: &[Command]) -> anyhow::Result<isize> {
-    let (x, d, _aim) = input.iter().fold((0, 0, 0), |(x, d, aim), cmd| match cmd {
-        Command::Forward(v) => (x + v, d + aim * v, aim),
-        Command::Down(v) => (x, d, aim + v),
-        Command::Up(v) => (x, d, aim - v),
-    });
-    Ok(x * d)
+    let s = trace(Aimed::default(), input)
+        .last()
+        .copied()
+        .unwrap_or_default();
+    Ok(s.x * s.depth)
 }
 
 #[derive(Debug, PartialEq)]
@@ -29,43 +188,37 @@ enum Command {
     Forward(isize),
     Down(isize),
     Up(isize),
+    Back(isize),
+    // Face the other way, forward and back swap directions
+    Turn,
 }
 
 fn parse(i: &str) -> anyhow::Result<Vec<Command>> {
-    let fwd = nom::combinator::map(
-        nom::sequence::preceded(
-            nom::sequence::pair(
-                nom::bytes::complete::tag("forward"),
-                nom::character::complete::space0,
-            ),
-            crate::helper::ival::<isize>,
-        ),
-        Command::Forward,
-    );
-    let down = nom::combinator::map(
-        nom::sequence::preceded(
-            nom::sequence::pair(
-                nom::bytes::complete::tag("down"),
-                nom::character::complete::space0,
-            ),
-            crate::helper::ival::<isize>,
-        ),
-        Command::Down,
-    );
-    let up = nom::combinator::map(
-        nom::sequence::preceded(
-            nom::sequence::pair(
-                nom::bytes::complete::tag("up"),
-                nom::character::complete::space0,
+    use nom::bytes::complete::tag;
+    use nom::combinator::map;
+
+    let cmd = |name: &'static str, f: fn(isize) -> Command| {
+        map(
+            nom::sequence::preceded(
+                nom::sequence::pair(tag(name), nom::character::complete::space0),
+                crate::helper::ival::<isize>,
             ),
-            crate::helper::ival::<isize>,
+            f,
+        )
+    };
+    let (_, res) = nom::combinator::all_consuming(nom::sequence::terminated(
+        nom::multi::separated_list1(
+            nom::character::complete::newline,
+            nom::branch::alt((
+                cmd("forward", Command::Forward),
+                cmd("down", Command::Down),
+                cmd("up", Command::Up),
+                cmd("back", Command::Back),
+                map(tag("turn"), |_| Command::Turn),
+            )),
         ),
-        Command::Up,
-    );
-    let (_, res) = nom::multi::separated_list1(
-        nom::character::complete::newline,
-        nom::branch::alt((fwd, down, up)),
-    )(i)
+        nom::character::complete::multispace0,
+    ))(i)
     .map_err(|e| e.to_owned())?;
     Ok(res)
 }
@@ -86,6 +239,60 @@ forward 2";
         assert_eq!(input.len(), 6);
         assert_eq!(input[0], super::Command::Forward(5));
         assert_eq!(input[1], super::Command::Down(5));
+        assert_eq!(super::parse("up 1\n\n").unwrap().len(), 1);
+        assert!(super::parse("forward 5\nbackward 3\nup 1").is_err());
+        assert!(super::parse("down x").is_err());
+    }
+
+    #[test]
+    fn aoc2_trace() {
+        use super::{Aimed, Simple, State};
+        let input = super::parse(INPUT).unwrap();
+        let states = super::trace(Aimed::default(), &input);
+        assert_eq!(states.len(), 6);
+        assert_eq!(
+            states[2],
+            State {
+                x: 13,
+                depth: 40,
+                aim: 5,
+                heading: 1
+            }
+        );
+        assert_eq!(super::max_depth(&states), 60);
+        assert_eq!(
+            super::max_depth(&super::trace(Simple::default(), &input)),
+            10
+        );
+    }
+
+    #[test]
+    fn aoc2_extra_commands() {
+        use super::{Aimed, Command, Simple};
+        let input = super::parse("forward 5\ndown 2\nturn\nforward 3\nback 1").unwrap();
+        assert_eq!(input[2], Command::Turn);
+        assert_eq!(input[4], Command::Back(1));
+        let simple = super::trace(Simple::default(), &input);
+        assert_eq!((simple[4].x, simple[4].depth), (3, 2));
+        let aimed = super::trace(Aimed::default(), &input);
+        assert_eq!((aimed[4].x, aimed[4].depth, aimed[4].heading), (3, 4, -1));
+    }
+
+    #[test]
+    fn aoc2_profile() {
+        let input = super::parse(INPUT).unwrap();
+        let states = super::trace(super::Simple::default(), &input);
+        assert_eq!(
+            super::profile(&states, 3),
+            "#~~#~~
+.##...
+....##"
+        );
+        let states = super::trace(
+            super::Simple::default(),
+            &super::parse("up 2\ndown 4").unwrap(),
+        );
+        assert_eq!(super::profile(&states, 3), "#.\n~~\n.#");
     }
 
     #[test]
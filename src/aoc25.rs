@@ -1,8 +1,15 @@
-use std::collections::HashMap;
 use std::fs;
 
-pub fn run() -> anyhow::Result<()> {
+pub fn run(args: &[String]) -> anyhow::Result<()> {
     let input = fs::read_to_string("day25.txt").unwrap();
+    // `25 --trace 0,1,58` prints the map after each of the listed steps
+    if let [flag, steps] = args {
+        if flag != "--trace" {
+            return Err(anyhow::anyhow!("Unknown option {}", flag));
+        }
+        print!("{}", trace(&input, &parse_steps(steps)?)?);
+        return Ok(());
+    }
     println!("day25-1: {}", run_1(&input)?);
     println!("day25-2: {}", run_2(&input)?);
     Ok(())
@@ -14,88 +21,147 @@ enum Dir {
     South,
 }
 
-// fn next_step(width: usize, height: usize) -> impl Fn(Dir, (usize, usize)) -> (usize, usize) {
-//     move |dir, (row, col)| match dir {
-//         Dir::East => (row, (col + 1) % width),
-//         Dir::South => ((row + 1) % height, col),
-//     }
-// }
-
 fn run_1(input: &str) -> anyhow::Result<usize> {
-    let (_, (width, height, mut map)) = parse(input).map_err(|e| e.to_owned())?;
-    let mut cnt = 0;
-
-    loop {
-        let mut cur = HashMap::new();
+    let mut map = parse(input)?;
+    let mut cnt = 1;
+    while map.step().total() > 0 {
         cnt += 1;
+    }
+    Ok(cnt)
+}
 
-        for ((row, col), _) in map.iter().filter(|((_, _), dir)| **dir == Dir::East) {
-            let next_col = (col + 1) % width;
-            if map.get(&(*row, next_col)).is_none() {
-                cur.insert((*row, next_col), Dir::East);
-            } else {
-                cur.insert((*row, *col), Dir::East);
-            }
-        }
+fn parse_steps(i: &str) -> anyhow::Result<Vec<usize>> {
+    let (_, steps) = nom::combinator::all_consuming(nom::multi::separated_list1(
+        nom::bytes::complete::tag(","),
+        crate::helper::uval,
+    ))(i)
+    .map_err(|e| e.to_owned())?;
+    Ok(steps)
+}
 
-        for ((row, col), _) in map.iter().filter(|((_, _), dir)| **dir == Dir::South) {
-            let next_row = (row + 1) % height;
-            let occupied = map.get(&(next_row, *col));
-            let occupied = match occupied {
-                Some(Dir::South) => true,
-                _ => cur.get(&(next_row, *col)).is_some(),
-            };
-            if !occupied {
-                cur.insert((next_row, *col), Dir::South);
-            } else {
-                cur.insert((*row, *col), Dir::South);
-            }
+// Renders the map after each of the given steps together with how many sea
+// cucumbers moved in that step, in the same layout as the puzzle text
+fn trace(input: &str, steps: &[usize]) -> anyhow::Result<String> {
+    use std::fmt::Write;
+    let mut map = parse(input)?;
+    let mut res = String::new();
+    let last = steps.iter().max().copied().unwrap_or(0);
+    for step in 0..=last {
+        let moves = if step > 0 {
+            map.step()
+        } else {
+            Moves::default()
+        };
+        if !steps.contains(&step) {
+            continue;
         }
-
-        if map == cur {
-            break;
+        match step {
+            0 => writeln!(res, "Initial state:")?,
+            1 => writeln!(
+                res,
+                "After 1 step (east {}, south {}):",
+                moves.east, moves.south
+            )?,
+            _ => writeln!(
+                res,
+                "After {} steps (east {}, south {}):",
+                step, moves.east, moves.south
+            )?,
         }
-
-        map = cur;
+        writeln!(res, "{}\n", map)?;
     }
-
-    Ok(cnt)
+    Ok(res)
 }
 
 fn run_2(_input: &str) -> anyhow::Result<usize> {
     todo!()
 }
 
-// fn parse_pos(i: &str) -> nom::IResult<&str, Option<Dir>> {
-//     let east = nom::combinator::map(nom::bytes::complete::tag(">"), |_| Some(Dir::East));
-//     let south = nom::combinator::map(nom::bytes::complete::tag("v"), |_| Some(Dir::South));
-//     let empty = nom::combinator::map(nom::bytes::complete::tag("."), |_| None);
-//     nom::branch::alt((east, south, empty))(i)
-// }
-
-type Map = HashMap<(usize, usize), Dir>;
-fn parse(i: &str) -> nom::IResult<&str, (usize, usize, Map)> {
-    let mut res = HashMap::new();
-    let mut width = 0;
-    let mut height = 0;
-    for (row, line) in i.lines().enumerate() {
-        height = height.max(row);
-        for (col, c) in line.chars().enumerate() {
-            width = width.max(col);
-            match c {
-                '>' => {
-                    res.insert((row, col), Dir::East);
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Moves {
+    east: usize,
+    south: usize,
+}
+
+impl Moves {
+    fn total(&self) -> usize {
+        self.east + self.south
+    }
+}
+
+// Row major grid, the second buffer is only used while stepping
+struct Map {
+    width: usize,
+    height: usize,
+    cells: Vec<Option<Dir>>,
+    next: Vec<Option<Dir>>,
+}
+
+impl Map {
+    // Moves one step and returns how many sea cucumbers of each herd moved
+    fn step(&mut self) -> Moves {
+        let east = self.move_herd(Dir::East);
+        let south = self.move_herd(Dir::South);
+        Moves { east, south }
+    }
+
+    fn move_herd(&mut self, dir: Dir) -> usize {
+        let mut moved = 0;
+        self.next.copy_from_slice(&self.cells);
+        for row in 0..self.height {
+            for col in 0..self.width {
+                if self.cells[row * self.width + col] != Some(dir) {
+                    continue;
                 }
-                'v' => {
-                    res.insert((row, col), Dir::South);
+                let (next_row, next_col) = match dir {
+                    Dir::East => (row, (col + 1) % self.width),
+                    Dir::South => ((row + 1) % self.height, col),
+                };
+                let target = next_row * self.width + next_col;
+                if self.cells[target].is_none() {
+                    self.next[row * self.width + col] = None;
+                    self.next[target] = Some(dir);
+                    moved += 1;
                 }
-                '.' => (),
-                _ => unreachable!(),
-            };
+            }
         }
+        std::mem::swap(&mut self.cells, &mut self.next);
+        moved
     }
+}
 
-    Ok(("", (width + 1, height + 1, res)))
+impl std::fmt::Display for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (row, cells) in self.cells.chunks(self.width).enumerate() {
+            if row > 0 {
+                writeln!(f)?;
+            }
+            for cell in cells {
+                let c = match cell {
+                    Some(Dir::East) => '>',
+                    Some(Dir::South) => 'v',
+                    None => '.',
+                };
+                write!(f, "{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse(i: &str) -> anyhow::Result<Map> {
+    let (width, height, cells) = crate::helper::grid(i, |c| match c {
+        '>' => Ok(Some(Dir::East)),
+        'v' => Ok(Some(Dir::South)),
+        '.' => Ok(None),
+        _ => Err(anyhow::anyhow!("Invalid map character {:?}", c)),
+    })?;
+    Ok(Map {
+        width,
+        height,
+        next: cells.clone(),
+        cells,
+    })
 }
 
 #[cfg(test)]
@@ -110,6 +176,65 @@ v>v.vv.v..
 v.v..>>v.v
 ....v..v.>";
 
+    #[test]
+    fn aoc25_parse() {
+        let map = super::parse(INPUT).unwrap();
+        assert_eq!((map.width, map.height), (10, 9));
+        assert!(super::parse("..>\n.v").is_err());
+        assert!(super::parse("..x").is_err());
+    }
+
+    #[test]
+    fn aoc25_step() {
+        let mut map = super::parse("...>>>>>...").unwrap();
+        assert_eq!(map.step().total(), 1);
+        assert_eq!(map.step().total(), 2);
+        assert_eq!(map.step().total(), 3);
+        assert_eq!(map.to_string(), "...>>.>.>.>");
+
+        // Wraps around and moves everything once nothing is blocked
+        let mut map = super::parse(">.\nv.").unwrap();
+        assert_eq!(map.step(), super::Moves { east: 1, south: 1 });
+        assert_eq!(map.to_string(), "v>\n..");
+    }
+
+    #[test]
+    fn aoc25_display() {
+        assert_eq!(super::parse(INPUT).unwrap().to_string(), INPUT);
+    }
+
+    #[test]
+    fn aoc25_trace() {
+        let res = super::trace(INPUT, &[1, 58]).unwrap();
+        let mut blocks = res.split("\n\n");
+        let first = blocks.next().unwrap();
+        assert!(first.starts_with("After 1 step (east "));
+        assert!(first.ends_with(
+            "....>.>v.>
+v.v>.>v.v.
+>v>>..>v..
+>>v>v>.>.v
+.>v.v...v.
+v>>.>vvv..
+..v...>>..
+vv...>>vv.
+>.v.v..v.v"
+        ));
+        assert_eq!(
+            blocks.next().unwrap(),
+            "After 58 steps (east 0, south 0):
+..>>v>vv..
+..v.>>vv..
+..>>v>>vv.
+..>>>>>vv.
+v......>vv
+v>v....>>v
+vvv.....>>
+>vv......>
+.>v.vv.v.."
+        );
+    }
+
     #[test]
     fn aoc25_run_1() {
         assert_eq!(super::run_1(INPUT).unwrap(), 58);
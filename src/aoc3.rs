@@ -8,86 +8,88 @@ pub fn run() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn run_1(input: &str) -> anyhow::Result<usize> {
-    let mut one_cnt = vec![0usize; 20];
-    let mut len = 0;
-
-    for line in input.lines() {
-        one_cnt.truncate(line.len());
-        len += 1;
-        // let mut idx = 0;
-        for (idx, v) in line.chars().rev().enumerate() {
-            if v == '1' {
-                one_cnt[idx] += 1;
-            }
-            // idx += 1;
-        }
-    }
-
-    let mut gamma = 0;
-    let mut epsilon = 0;
+// The diagnostic numbers sorted, all with the same number of bits
+#[derive(Debug, PartialEq)]
+struct Report {
+    width: u32,
+    values: Vec<u64>,
+}
 
-    for (idx, i) in one_cnt.iter().enumerate() {
-        if *i > (len / 2) {
-            gamma += 1 << idx;
-        } else {
-            epsilon += 1 << idx;
+fn parse(input: &str) -> anyhow::Result<Report> {
+    let mut width = None;
+    let mut values = Vec::new();
+    for line in input.lines().filter(|l| !l.is_empty()) {
+        if line.len() > 64 || line.chars().any(|c| c != '0' && c != '1') {
+            return Err(anyhow::anyhow!("Invalid diagnostic number {:?}", line));
+        }
+        if *width.get_or_insert(line.len()) != line.len() {
+            return Err(anyhow::anyhow!("{:?} has the wrong width", line));
         }
+        values.push(u64::from_str_radix(line, 2)?);
     }
-    Ok(epsilon * gamma)
+    let width = width.ok_or_else(|| anyhow::anyhow!("Empty report"))? as u32;
+    values.sort_unstable();
+    Ok(Report { width, values })
 }
 
-fn oxygen_rating(input: Vec<Vec<u32>>, idx: usize) -> Vec<u32> {
-    if input.len() == 1 {
-        return input[0].clone();
+impl Report {
+    fn ones(&self, bit: u32) -> usize {
+        self.values.iter().filter(|v| *v >> bit & 1 == 1).count()
     }
 
-    let num_ones = input.iter().filter(|line| line[idx] == 1).count();
-    let filter = (num_ones >= (input.len() - num_ones)) as u32;
-    let input = input
-        .into_iter()
-        .filter(|line| line[idx] == filter)
-        .collect();
-    oxygen_rating(input, idx + 1)
-}
+    // Most common value of each bit, ties go to 0
+    fn gamma(&self) -> u64 {
+        (0..self.width)
+            .filter(|bit| self.ones(*bit) * 2 > self.values.len())
+            .fold(0, |acc, bit| acc | 1 << bit)
+    }
 
-fn co2_rating(input: Vec<Vec<u32>>, idx: usize) -> Vec<u32> {
-    if input.len() == 1 {
-        return input[0].clone();
+    fn epsilon(&self) -> u64 {
+        !self.gamma() & (u64::MAX >> (64 - self.width))
     }
 
-    let num_ones = input.iter().filter(|line| line[idx] == 1).count();
-    let filter = (num_ones < (input.len() - num_ones)) as u32;
-    let input = input
-        .into_iter()
-        .filter(|line| line[idx] == filter)
-        .collect();
-    co2_rating(input, idx + 1)
-}
+    // Narrows down the values one bit at a time from the top. The values
+    // left always share their higher bits, so they form a range of the
+    // sorted list with the ones that have a 0 in the current bit first.
+    // `keep_ones(ones, zeros)` picks the half to keep.
+    fn rating(&self, keep_ones: impl Fn(usize, usize) -> bool) -> u64 {
+        let (mut lo, mut hi) = (0, self.values.len());
+        for bit in (0..self.width).rev() {
+            if hi - lo == 1 {
+                break;
+            }
+            let split = lo + self.values[lo..hi].partition_point(|v| *v >> bit & 1 == 0);
+            let (zeros, ones) = (split - lo, hi - split);
+            // A bit every value agrees on doesn't filter anything
+            if zeros == 0 || ones == 0 {
+                continue;
+            }
+            if keep_ones(ones, zeros) {
+                lo = split;
+            } else {
+                hi = split;
+            }
+        }
+        self.values[lo]
+    }
 
-fn run_2(input: &str) -> anyhow::Result<u32> {
-    let input: Vec<Vec<_>> = input
-        .lines()
-        .map(|line| {
-            line.chars()
-                .map(|c| c.to_digit(10).unwrap() as u32)
-                .collect()
-        })
-        .collect();
-
-    let oxy = oxygen_rating(input.clone(), 0);
-    let mut oxy_val = 0;
-    for o in oxy.iter() {
-        oxy_val = (oxy_val << 1) + o;
+    fn oxygen(&self) -> u64 {
+        self.rating(|ones, zeros| ones >= zeros)
     }
 
-    let co2 = co2_rating(input, 0);
-    let mut co2_val = 0;
-    for c in co2.iter() {
-        co2_val = (co2_val << 1) + c;
+    fn co2(&self) -> u64 {
+        self.rating(|ones, zeros| ones < zeros)
     }
+}
 
-    Ok(co2_val * oxy_val)
+fn run_1(input: &str) -> anyhow::Result<u64> {
+    let report = parse(input)?;
+    Ok(report.gamma() * report.epsilon())
+}
+
+fn run_2(input: &str) -> anyhow::Result<u64> {
+    let report = parse(input)?;
+    Ok(report.oxygen() * report.co2())
 }
 
 #[cfg(test)]
@@ -105,6 +107,29 @@ mod tests {
 00010
 01010";
 
+    #[test]
+    fn aoc3_parse() {
+        let report = super::parse(INPUT).unwrap();
+        assert_eq!(report.width, 5);
+        assert_eq!(report.values[..3], [0b00010, 0b00100, 0b00111]);
+        assert!(super::parse("").is_err());
+        assert!(super::parse("101\n11").is_err());
+        assert!(super::parse("102").is_err());
+        assert!(super::parse(&"1".repeat(65)).is_err());
+        assert_eq!(super::parse(&"1".repeat(64)).unwrap().values, [u64::MAX]);
+    }
+
+    #[test]
+    fn aoc3_report() {
+        let report = super::parse(INPUT).unwrap();
+        assert_eq!((report.gamma(), report.epsilon()), (22, 9));
+        assert_eq!((report.oxygen(), report.co2()), (23, 10));
+
+        let report = super::parse("0110\n0110\n0111").unwrap();
+        assert_eq!((report.gamma(), report.epsilon()), (0b0110, 0b1001));
+        assert_eq!((report.oxygen(), report.co2()), (0b0110, 0b0111));
+    }
+
     #[test]
     fn aoc3_run_1() {
         assert_eq!(super::run_1(INPUT).unwrap(), 198);
@@ -1,98 +1,196 @@
 use std::fs;
 
-pub fn run() -> anyhow::Result<()> {
+pub fn run(args: &[String]) -> anyhow::Result<()> {
     let input = fs::read_to_string("day4.txt").unwrap();
-    println!("4:1 {}", run_1(&input)?);
-    println!("4:2 {}", run_2(&input)?);
+    // `4 --diagonals` lets full diagonals win too and `--winner k` prints
+    // the k-th board to win, counting from 1
+    let mut diagonals = false;
+    let mut winner = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--diagonals" => diagonals = true,
+            "--winner" => {
+                let k = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--winner needs a position"))?;
+                winner = Some(k.parse::<usize>()?);
+            }
+            _ => return Err(anyhow::anyhow!("Unknown option {}", arg)),
+        }
+    }
+    if !diagonals && winner.is_none() {
+        println!("4:1 {}", run_1(&input)?);
+        println!("4:2 {}", run_2(&input)?);
+        return Ok(());
+    }
+
+    let (draws, boards) = parse(&input)?;
+    let wins = Bingo::new(boards, diagonals).play(&draws);
+    let k = winner.unwrap_or(1);
+    let win = k
+        .checked_sub(1)
+        .and_then(|k| wins.get(k))
+        .ok_or_else(|| anyhow::anyhow!("Only {} boards win", wins.len()))?;
+    println!(
+        "4: board {} wins {} on draw {} with {}, score {}",
+        win.board + 1,
+        ordinal(k),
+        win.draw + 1,
+        win.number,
+        win.score
+    );
     Ok(())
 }
 
-fn check_board(input: usize, board: &mut [Vec<usize>]) -> bool {
-    let mut found = None;
-    for (r_num, row) in board.iter_mut().enumerate() {
-        for (c_num, val) in row.iter_mut().enumerate() {
-            if *val == input {
-                *val = usize::MAX;
-                found = Some((r_num, c_num));
-                break;
-            }
+fn ordinal(k: usize) -> String {
+    let suffix = match (k % 10, k % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", k, suffix)
+}
+
+// Row major N x N numbers with the ones drawn so far marked
+#[derive(Debug, Clone, PartialEq)]
+struct Board {
+    size: usize,
+    numbers: Vec<usize>,
+    marked: Vec<bool>,
+}
+
+impl Board {
+    fn new(rows: Vec<Vec<usize>>) -> anyhow::Result<Self> {
+        let size = rows.len();
+        if rows.iter().any(|r| r.len() != size) {
+            return Err(anyhow::anyhow!("Board is not square"));
         }
+        Ok(Board {
+            size,
+            numbers: rows.into_iter().flatten().collect(),
+            marked: vec![false; size * size],
+        })
+    }
+
+    // Marks `number` and returns true if that completes a line
+    fn mark(&mut self, number: usize, diagonals: bool) -> bool {
+        let Some(idx) = self.numbers.iter().position(|n| *n == number) else {
+            return false;
+        };
+        self.marked[idx] = true;
+        let (row, col) = (idx / self.size, idx % self.size);
+        let full =
+            |mut cells: std::ops::Range<usize>, step: usize| cells.all(|i| self.marked[i * step]);
+        let n = self.size;
+        (0..n).all(|c| self.marked[row * n + c])
+            || (0..n).all(|r| self.marked[r * n + col])
+            || (diagonals && row == col && full(0..n, n + 1))
+            || (diagonals && row + col == n - 1 && full(1..n + 1, n - 1))
     }
 
-    if let Some((row, col)) = found {
-        // Check if winning row
-        board[row].iter().all(|v| *v == usize::MAX)
-            || board.iter().map(|row| row[col]).all(|v| v == usize::MAX)
-    } else {
-        false
+    fn unmarked_sum(&self) -> usize {
+        self.numbers
+            .iter()
+            .zip(self.marked.iter())
+            .filter(|(_, m)| !**m)
+            .map(|(n, _)| n)
+            .sum()
     }
 }
 
-fn run_1(input: &str) -> anyhow::Result<usize> {
-    let (input, mut boards) = parse(input)?;
+#[derive(Debug, PartialEq)]
+struct Win {
+    board: usize,
+    // Index into the draws
+    draw: usize,
+    number: usize,
+    score: usize,
+}
 
-    for i in input.iter() {
-        for board in boards.iter_mut() {
-            if check_board(*i, board) {
-                let sum: usize = board.iter().flatten().filter(|v| **v != usize::MAX).sum();
-                return Ok(i * sum);
-            }
-        }
-    }
-    Ok(0)
+struct Bingo {
+    boards: Vec<Board>,
+    // Full diagonals count as a win, as well as rows and columns
+    diagonals: bool,
 }
 
-fn run_2(input: &str) -> anyhow::Result<usize> {
-    let (input, mut boards) = parse(input)?;
-
-    while boards.len() > 1 {
-        for i in input.iter() {
-            boards.retain(|b| !b.is_empty());
-            let boards_left = boards.len();
-            for board in boards.iter_mut() {
-                if check_board(*i, board) {
-                    if boards_left == 1 {
-                        let sum: usize = board.iter().flatten().filter(|v| **v != usize::MAX).sum();
-                        return Ok(i * sum);
-                    } else {
-                        board.truncate(0);
-                    }
+impl Bingo {
+    fn new(boards: Vec<Board>, diagonals: bool) -> Self {
+        Bingo { boards, diagonals }
+    }
+
+    // Every board that wins, in the order they win. Boards stop being
+    // marked once they have won.
+    fn play(mut self, draws: &[usize]) -> Vec<Win> {
+        let mut won = vec![false; self.boards.len()];
+        let mut wins = Vec::new();
+        for (draw, number) in draws.iter().enumerate() {
+            for (board, b) in self.boards.iter_mut().enumerate() {
+                if !won[board] && b.mark(*number, self.diagonals) {
+                    won[board] = true;
+                    wins.push(Win {
+                        board,
+                        draw,
+                        number: *number,
+                        score: number * b.unmarked_sum(),
+                    });
                 }
             }
+            if wins.len() == self.boards.len() {
+                break;
+            }
         }
+        wins
     }
-    Ok(0)
 }
 
-type Board = Vec<Vec<usize>>;
+fn run_1(input: &str) -> anyhow::Result<usize> {
+    let (draws, boards) = parse(input)?;
+    let wins = Bingo::new(boards, false).play(&draws);
+    wins.first()
+        .map(|w| w.score)
+        .ok_or_else(|| anyhow::anyhow!("No board wins"))
+}
+
+fn run_2(input: &str) -> anyhow::Result<usize> {
+    let (draws, boards) = parse(input)?;
+    let wins = Bingo::new(boards, false).play(&draws);
+    wins.last()
+        .map(|w| w.score)
+        .ok_or_else(|| anyhow::anyhow!("No board wins"))
+}
+
+// The draws and the boards, which can be any size as long as they are all
+// square and the same size
 fn parse(i: &str) -> anyhow::Result<(Vec<usize>, Vec<Board>)> {
-    // get input numbers
-    let (i, input) =
-        nom::multi::separated_list1(nom::bytes::complete::tag(","), crate::helper::uval)(i)
-            .map_err(|e| e.to_owned())?;
-
-    let (i, _) = nom::multi::many1(nom::character::complete::newline)(i)
-        .map_err(|e: nom::Err<nom::error::Error<&str>>| e.to_owned())?;
-
-    let parse_row = |i| {
-        nom::multi::count(
-            nom::sequence::preceded(nom::character::complete::space0, crate::helper::uval),
-            5,
-        )(i)
-    };
+    use nom::character::complete::{newline, space0, space1};
+    use nom::multi::{many1, separated_list1};
+    use nom::sequence::{preceded, terminated};
 
-    let board = nom::multi::count(
-        nom::sequence::terminated(
-            parse_row,
-            nom::combinator::opt(nom::character::complete::newline),
-        ),
-        5,
-    );
+    let (i, draws) = terminated(
+        separated_list1(nom::bytes::complete::tag(","), crate::helper::uval),
+        many1(newline),
+    )(i)
+    .map_err(|e: nom::Err<nom::error::Error<&str>>| e.to_owned())?;
 
-    let (_i, boards) = nom::multi::separated_list1(nom::character::complete::newline, board)(i)
-        .map_err(|e| e.to_owned())?;
+    let row = preceded(space0, separated_list1(space1, crate::helper::uval));
+    let board = many1(terminated(row, nom::combinator::opt(newline)));
+    let (_, boards) = nom::combinator::all_consuming(terminated(
+        separated_list1(newline, board),
+        nom::character::complete::multispace0,
+    ))(i)
+    .map_err(|e| e.to_owned())?;
 
-    Ok((input, boards))
+    let boards = boards
+        .into_iter()
+        .map(Board::new)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if boards.iter().any(|b| b.size != boards[0].size) {
+        return Err(anyhow::anyhow!("Boards have different sizes"));
+    }
+    Ok((draws, boards))
 }
 
 #[cfg(test)]
@@ -121,6 +219,38 @@ mod tests {
     fn aoc4_parse() {
         let (_input, boards) = super::parse(INPUT).unwrap();
         assert_eq!(boards.len(), 3);
+        assert_eq!(boards[1].size, 5);
+        assert_eq!(boards[1].numbers[5], 9);
+        assert!(super::parse("1,2\n\n1 2\n3 4\n\n1 2 3\n4 5 6\n7 8 9").is_err());
+        assert!(super::parse("1,2\n\n1 2\n3").is_err());
+    }
+
+    #[test]
+    fn aoc4_play() {
+        let (draws, boards) = super::parse(INPUT).unwrap();
+        let wins = super::Bingo::new(boards, false).play(&draws);
+        assert_eq!(
+            wins.iter()
+                .map(|w| (w.board, w.draw, w.number))
+                .collect::<Vec<_>>(),
+            vec![(2, 11, 24), (0, 13, 16), (1, 14, 13)]
+        );
+        assert_eq!((wins[0].score, wins[2].score), (4512, 1924));
+    }
+
+    #[test]
+    fn aoc4_diagonals() {
+        let input = "5,1,9,3\n\n1 2 3\n4 5 6\n7 8 9\n\n2 1 3\n6 5 4\n9 8 7";
+        let (draws, boards) = super::parse(input).unwrap();
+        assert!(super::Bingo::new(boards.clone(), false)
+            .play(&draws)
+            .is_empty());
+        let wins = super::Bingo::new(boards, true).play(&draws);
+        assert_eq!(
+            wins.iter().map(|w| (w.board, w.draw)).collect::<Vec<_>>(),
+            vec![(0, 2), (1, 3)]
+        );
+        assert_eq!(wins[0].score, 9 * (2 + 3 + 4 + 6 + 7 + 8));
     }
 
     #[test]
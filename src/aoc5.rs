@@ -1,65 +1,282 @@
 use std::fs;
 
-pub fn run() -> anyhow::Result<()> {
+pub fn run(args: &[String]) -> anyhow::Result<()> {
     let input = fs::read_to_string("day5.txt").unwrap();
+    if !args.is_empty() {
+        return inspect(&input, args);
+    }
 
     println!("5:1 {}", run_1(&input)?);
     println!("5:2 {}", run_2(&input)?);
     Ok(())
 }
 
-fn run_1(input: &str) -> anyhow::Result<usize> {
-    let lines: Vec<_> = parse(input)?;
-
-    let mut coord_cnt = std::collections::HashMap::new();
-
-    for (c1, c2) in lines.iter() {
-        // vertical
-        if c1.0 == c2.0 {
-            // println!("{},{} -> {},{}", c1.0, c1.1, c2.0, c2.1);
-            let x = c1.0;
-            for y in c1.1.min(c2.1)..=c1.1.max(c2.1) {
-                // println!("\t{},{}", x, y);
-                *coord_cnt.entry((x, y)).or_insert(0) += 1;
+// `--lines any` also draws lines that are neither axis aligned nor 45
+// degrees, `axis` and `diagonal` match part 1 and 2 which is the default.
+// `--render` prints the diagram, `--max` the highest overlap and where it
+// is, and `--image <file>` writes a PGM, or a PPM if the name ends in .ppm.
+fn inspect(input: &str, args: &[String]) -> anyhow::Result<()> {
+    let mut kind = Lines::Diagonal;
+    let mut render = false;
+    let mut max = false;
+    let mut image = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow::anyhow!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--lines" => {
+                kind = match value()?.as_str() {
+                    "axis" => Lines::AxisAligned,
+                    "diagonal" => Lines::Diagonal,
+                    "any" => Lines::Any,
+                    k => return Err(anyhow::anyhow!("Unknown kind of lines {}", k)),
+                }
             }
+            "--render" => render = true,
+            "--max" => max = true,
+            "--image" => image = Some(value()?.clone()),
+            _ => return Err(anyhow::anyhow!("Unknown option {}", arg)),
         }
-        // horizontal
-        else if c1.1 == c2.1 {
-            let y = c1.1;
-            for x in c1.0.min(c2.0)..=c1.0.max(c2.0) {
-                *coord_cnt.entry((x, y)).or_insert(0) += 1;
-            }
+    }
+
+    let diagram = Diagram::new(&parse(input)?, kind);
+    println!("5: {}", diagram.overlaps());
+    if render {
+        println!("{}", diagram.render()?);
+    }
+    if max {
+        if let Some((count, points)) = diagram.max_overlap() {
+            println!("max overlap {} at {:?}", count, points);
         }
     }
+    if let Some(file) = image {
+        fs::write(&file, diagram.image(file.ends_with(".ppm"))?)?;
+    }
+    Ok(())
+}
 
-    Ok(coord_cnt.iter().filter(|(_, v)| **v >= 2).count())
+fn run_1(input: &str) -> anyhow::Result<usize> {
+    Ok(Diagram::new(&parse(input)?, Lines::AxisAligned).overlaps())
 }
 
 fn run_2(input: &str) -> anyhow::Result<usize> {
-    let lines: Vec<_> = parse(input)?;
-
-    let mut coord_cnt = std::collections::HashMap::new();
-
-    for (c1, c2) in lines.iter() {
-        // vertical
-        if c1.0 == c2.0 {
-            // println!("{},{} -> {},{}", c1.0, c1.1, c2.0, c2.1);
-            let x = c1.0;
-            for y in c1.1.min(c2.1)..=c1.1.max(c2.1) {
-                // println!("\t{},{}", x, y);
-                *coord_cnt.entry((x, y)).or_insert(0) += 1;
-            }
-        } else {
-            let (c1, c2) = if c1.0 < c2.0 { (c1, c2) } else { (c2, c1) };
-            let k = (c2.1 - c1.1) / (c2.0 - c1.0);
-            for x in c1.0..=c2.0 {
-                let y = c1.1 + k * (x - c1.0);
-                *coord_cnt.entry((x, y)).or_insert(0) += 1;
+    Ok(Diagram::new(&parse(input)?, Lines::Diagonal).overlaps())
+}
+
+// Which lines to draw
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Lines {
+    AxisAligned,
+    // Axis aligned and 45 degree lines
+    Diagonal,
+    Any,
+}
+
+impl Lines {
+    fn accepts(&self, (c1, c2): &(Coord, Coord)) -> bool {
+        let (dx, dy) = ((c2.0 - c1.0).abs(), (c2.1 - c1.1).abs());
+        match self {
+            Lines::AxisAligned => dx == 0 || dy == 0,
+            Lines::Diagonal => dx == 0 || dy == 0 || dx == dy,
+            Lines::Any => true,
+        }
+    }
+}
+
+fn gcd(a: isize, b: isize) -> isize {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// The points with integer coordinates on the line, both ends included.
+// Stepping by the direction divided by the gcd of its components hits
+// every one of them for any slope.
+fn points((c1, c2): &(Coord, Coord)) -> impl Iterator<Item = Coord> {
+    let (dx, dy) = (c2.0 - c1.0, c2.1 - c1.1);
+    let steps = gcd(dx, dy);
+    let (sx, sy) = if steps == 0 {
+        (0, 0)
+    } else {
+        (dx / steps, dy / steps)
+    };
+    let start = *c1;
+    (0..=steps).map(move |k| (start.0 + k * sx, start.1 + k * sy))
+}
+
+// Inputs spanning at most this many cells are counted in a flat array
+const DENSE_LIMIT: usize = 1 << 24;
+
+enum Counts {
+    // Row major over the bounding box
+    Dense(Vec<u32>),
+    Sparse(std::collections::HashMap<Coord, u32>),
+}
+
+// How many lines cover each point, within the bounding box of the lines
+struct Diagram {
+    min: Coord,
+    max: Coord,
+    counts: Counts,
+}
+
+impl Diagram {
+    fn new(lines: &[(Coord, Coord)], kind: Lines) -> Self {
+        let lines = lines.iter().filter(|l| kind.accepts(l)).collect::<Vec<_>>();
+        let xs = lines.iter().flat_map(|(c1, c2)| [c1.0, c2.0]);
+        let ys = lines.iter().flat_map(|(c1, c2)| [c1.1, c2.1]);
+        let min = (xs.clone().min().unwrap_or(0), ys.clone().min().unwrap_or(0));
+        let max = (xs.max().unwrap_or(0), ys.max().unwrap_or(0));
+
+        let mut diagram = Diagram {
+            min,
+            max,
+            counts: Counts::Sparse(std::collections::HashMap::new()),
+        };
+        let (width, height) = diagram.size();
+        if let Some(cells) = width.checked_mul(height).filter(|c| *c <= DENSE_LIMIT) {
+            diagram.counts = Counts::Dense(vec![0; cells]);
+        }
+        for p in lines.into_iter().flat_map(points) {
+            diagram.add(p);
+        }
+        diagram
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (
+            self.max.0.abs_diff(self.min.0).saturating_add(1),
+            self.max.1.abs_diff(self.min.1).saturating_add(1),
+        )
+    }
+
+    // Position in the dense counts. Only computed for those, a sparse
+    // bounding box may be too big to index.
+    fn index(min: Coord, width: usize, (x, y): Coord) -> usize {
+        y.abs_diff(min.1) * width + x.abs_diff(min.0)
+    }
+
+    fn add(&mut self, p: Coord) {
+        let (min, width) = (self.min, self.size().0);
+        match &mut self.counts {
+            Counts::Dense(counts) => counts[Self::index(min, width, p)] += 1,
+            Counts::Sparse(counts) => *counts.entry(p).or_insert(0) += 1,
+        }
+    }
+
+    fn count(&self, p: Coord) -> u32 {
+        match &self.counts {
+            Counts::Dense(counts) => counts[Self::index(self.min, self.size().0, p)],
+            Counts::Sparse(counts) => counts.get(&p).copied().unwrap_or(0),
+        }
+    }
+
+    // Drawing walks every point of the bounding box
+    fn drawable(&self) -> anyhow::Result<()> {
+        let (width, height) = self.size();
+        match width.checked_mul(height) {
+            Some(cells) if cells <= DENSE_LIMIT => Ok(()),
+            _ => Err(anyhow::anyhow!(
+                "Diagram of {}x{} points is too big to draw",
+                width,
+                height
+            )),
+        }
+    }
+
+    // Every point covered by at least one line with its count
+    fn covered(&self) -> Box<dyn Iterator<Item = (Coord, u32)> + '_> {
+        match &self.counts {
+            Counts::Dense(counts) => {
+                let width = self.size().0;
+                Box::new(
+                    counts
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, c)| **c > 0)
+                        .map(move |(idx, c)| {
+                            let x = self.min.0 + (idx % width) as isize;
+                            let y = self.min.1 + (idx / width) as isize;
+                            ((x, y), *c)
+                        }),
+                )
             }
+            Counts::Sparse(counts) => Box::new(counts.iter().map(|(p, c)| (*p, *c))),
         }
     }
 
-    Ok(coord_cnt.iter().filter(|(_, v)| **v >= 2).count())
+    // Points covered by at least two lines
+    fn overlaps(&self) -> usize {
+        self.covered().filter(|(_, c)| *c >= 2).count()
+    }
+
+    // The highest count and every point that has it, sorted by row
+    fn max_overlap(&self) -> Option<(u32, Vec<Coord>)> {
+        let max = self.covered().map(|(_, c)| c).max()?;
+        let mut points = self
+            .covered()
+            .filter(|(_, c)| *c == max)
+            .map(|(p, _)| p)
+            .collect::<Vec<_>>();
+        points.sort_by_key(|(x, y)| (*y, *x));
+        Some((max, points))
+    }
+
+    // The diagram like the puzzle draws it, counts above 9 are shown as #
+    fn render(&self) -> anyhow::Result<String> {
+        self.drawable()?;
+        Ok((self.min.1..=self.max.1)
+            .map(|y| {
+                (self.min.0..=self.max.0)
+                    .map(|x| match self.count((x, y)) {
+                        0 => '.',
+                        c if c > 9 => '#',
+                        c => (b'0' + c as u8) as char,
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    // Binary PGM, or PPM with `colour`, with brighter pixels for more
+    // overlap. Colours go from black through red and yellow to white.
+    fn image(&self, colour: bool) -> anyhow::Result<Vec<u8>> {
+        self.drawable()?;
+        let (width, height) = self.size();
+        let max = self.max_overlap().map_or(1, |(m, _)| m) as usize;
+        let mut res = format!(
+            "{}\n{} {}\n255\n",
+            if colour { "P6" } else { "P5" },
+            width,
+            height
+        )
+        .into_bytes();
+        for y in self.min.1..=self.max.1 {
+            for x in self.min.0..=self.max.0 {
+                let level = self.count((x, y)) as usize * 255 / max;
+                if colour {
+                    let heat = level * 3;
+                    res.extend(
+                        [
+                            heat.min(255),
+                            heat.saturating_sub(255).min(255),
+                            heat.saturating_sub(510),
+                        ]
+                        .map(|c| c as u8),
+                    );
+                } else {
+                    res.push(level as u8);
+                }
+            }
+        }
+        Ok(res)
+    }
 }
 
 type Coord = (isize, isize);
@@ -102,6 +319,104 @@ mod tests {
         assert_eq!(lines[9].1, (8, 2));
     }
 
+    #[test]
+    fn aoc5_points() {
+        let points = |c1, c2| super::points(&(c1, c2)).collect::<Vec<_>>();
+        assert_eq!(points((1, 1), (1, 3)), vec![(1, 1), (1, 2), (1, 3)]);
+        assert_eq!(points((9, 7), (7, 9)), vec![(9, 7), (8, 8), (7, 9)]);
+        assert_eq!(
+            points((0, 0), (6, -3)),
+            vec![(0, 0), (2, -1), (4, -2), (6, -3)]
+        );
+        assert_eq!(points((0, 0), (2, 3)), vec![(0, 0), (2, 3)]);
+        assert_eq!(points((4, 4), (4, 4)), vec![(4, 4)]);
+    }
+
+    #[test]
+    fn aoc5_diagram() {
+        use super::{Diagram, Lines};
+        let lines = vec![((0, 0), (4, 2)), ((2, 0), (2, 4)), ((0, 0), (2, 1))];
+        assert_eq!(Diagram::new(&lines, Lines::AxisAligned).overlaps(), 0);
+        assert_eq!(Diagram::new(&lines, Lines::Diagonal).overlaps(), 0);
+        // (0, 0) twice and (2, 1) three times
+        assert_eq!(Diagram::new(&lines, Lines::Any).overlaps(), 2);
+
+        // Far apart coordinates fall back to counting in a map
+        let lines = vec![
+            ((0, 0), (0, 5)),
+            ((0, 3), (0, 2)),
+            ((5000, 5000), (5001, 5000)),
+        ];
+        let diagram = Diagram::new(&lines, Lines::Any);
+        assert!(matches!(diagram.counts, super::Counts::Sparse(_)));
+        assert_eq!(diagram.overlaps(), 2);
+
+        // Too far apart to index the bounding box at all
+        let far = 5_000_000_000;
+        let lines = vec![
+            ((0, 0), (0, 1)),
+            ((far, far), (far, far + 1)),
+            ((0, 1), (1, 1)),
+        ];
+        let diagram = Diagram::new(&lines, Lines::Any);
+        assert_eq!(diagram.overlaps(), 1);
+        assert_eq!(diagram.count((0, 1)), 2);
+        assert_eq!(diagram.max_overlap(), Some((2, vec![(0, 1)])));
+        assert!(diagram.render().is_err());
+        assert!(diagram.image(false).is_err());
+    }
+
+    #[test]
+    fn aoc5_render() {
+        use super::{Diagram, Lines};
+        let lines = super::parse(INPUT).unwrap();
+        assert_eq!(
+            Diagram::new(&lines, Lines::AxisAligned).render().unwrap(),
+            ".......1..
+..1....1..
+..1....1..
+.......1..
+.112111211
+..........
+..........
+..........
+..........
+222111...."
+        );
+        let diagram = Diagram::new(&lines, Lines::Diagonal);
+        assert_eq!(
+            diagram.render().unwrap(),
+            "1.1....11.
+.111...2..
+..2.1.111.
+...1.2.2..
+.112313211
+...1.2....
+..1...1...
+.1.....1..
+1.......1.
+222111...."
+        );
+        assert_eq!(diagram.max_overlap(), Some((3, vec![(4, 4), (6, 4)])));
+        assert_eq!(Diagram::new(&[], Lines::Any).max_overlap(), None);
+    }
+
+    #[test]
+    fn aoc5_image() {
+        use super::{Diagram, Lines};
+        let diagram = Diagram::new(&[((0, 0), (2, 0)), ((1, 0), (1, 0))], Lines::Any);
+        assert_eq!(
+            diagram.image(false).unwrap(),
+            b"P5\n3 1\n255\n\x7f\xff\x7f".to_vec()
+        );
+        let ppm = diagram.image(true).unwrap();
+        assert!(ppm.starts_with(b"P6\n3 1\n255\n"));
+        assert_eq!(
+            ppm[ppm.len() - 9..],
+            [255, 126, 0, 255, 255, 255, 255, 126, 0]
+        );
+    }
+
     #[test]
     fn aoc5_run_1() {
         assert_eq!(super::run_1(INPUT).unwrap(), 5);
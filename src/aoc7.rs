@@ -1,48 +1,156 @@
 use std::fs;
 
-pub fn run() -> anyhow::Result<()> {
+pub fn run(args: &[String]) -> anyhow::Result<()> {
     let input = fs::read_to_string("day7.txt")?;
+    // `7 --cost quadratic` aligns with the cost growing as distance squared,
+    // `--cost power:k` with distance to the power of k
+    if let [flag, cost] = args {
+        if flag != "--cost" {
+            return Err(anyhow::anyhow!("Unknown option {}", flag));
+        }
+        let positions = parse(&input)?;
+        let best = match cost.as_str() {
+            "linear" => align(&positions, &Linear),
+            "triangular" => align(&positions, &Triangular),
+            "quadratic" => align(&positions, &Quadratic),
+            _ => match cost.strip_prefix("power:") {
+                Some(k) => {
+                    let k = k.parse::<u32>()?;
+                    align(&positions, &Convex(move |d: isize| d.pow(k)))
+                }
+                None => return Err(anyhow::anyhow!("Unknown cost {}", cost)),
+            },
+        };
+        println!("day7: {} fuel at {}", best.fuel, best.position);
+        return Ok(());
+    }
     println!("day7-1: {}", run_1(&input)?);
     println!("day7-2: {}", run_2(&input)?);
     Ok(())
 }
 
-pub fn run_1(input: &str) -> anyhow::Result<isize> {
-    let mut positions = parse(input)?;
-    positions.sort_unstable();
+// Fuel for moving one crab `distance` steps
+trait Cost {
+    fn cost(&self, distance: isize) -> isize;
+
+    // If the cost never grows slower the further a crab moves, the total
+    // fuel has a single minimum and can be searched for instead of scanned
+    fn convex(&self) -> bool {
+        true
+    }
 
-    let median_pos: isize = positions[positions.len() / 2];
+    // Positions the best one is known to be in, given sorted crabs
+    fn bounds(&self, positions: &[isize]) -> (isize, isize) {
+        (positions[0], positions[positions.len() - 1])
+    }
+}
 
-    let fuel: isize = positions.iter().map(|p| (p - median_pos).abs()).sum();
+struct Linear;
+struct Triangular;
+struct Quadratic;
 
-    Ok(fuel)
+fn mean(positions: &[isize]) -> f64 {
+    positions.iter().sum::<isize>() as f64 / positions.len() as f64
 }
 
-fn fuel_2(from: isize, to: isize) -> isize {
-    let low = to.min(from);
-    let high = to.max(from) - low;
+impl Cost for Linear {
+    fn cost(&self, distance: isize) -> isize {
+        distance
+    }
 
-    (high + 1) * high / 2
+    // Any median is optimal
+    fn bounds(&self, positions: &[isize]) -> (isize, isize) {
+        let median = positions[positions.len() / 2];
+        (median, median)
+    }
 }
 
-pub fn run_2(input: &str) -> anyhow::Result<isize> {
-    let mut positions = parse(input)?;
+impl Cost for Triangular {
+    fn cost(&self, distance: isize) -> isize {
+        (distance + 1) * distance / 2
+    }
+
+    // The best position is within half a step of the mean
+    fn bounds(&self, positions: &[isize]) -> (isize, isize) {
+        let mean = mean(positions);
+        ((mean - 0.5).floor() as isize, (mean + 0.5).ceil() as isize)
+    }
+}
+
+impl Cost for Quadratic {
+    fn cost(&self, distance: isize) -> isize {
+        distance * distance
+    }
+
+    fn bounds(&self, positions: &[isize]) -> (isize, isize) {
+        let mean = mean(positions);
+        (mean.floor() as isize, mean.ceil() as isize)
+    }
+}
+
+// Any other cost, without knowing its shape every position is tried
+impl<F: Fn(isize) -> isize> Cost for F {
+    fn cost(&self, distance: isize) -> isize {
+        self(distance)
+    }
+
+    fn convex(&self) -> bool {
+        false
+    }
+}
+
+// A cost known to be convex, searched for between the outermost crabs
+struct Convex<F>(F);
+
+impl<F: Fn(isize) -> isize> Cost for Convex<F> {
+    fn cost(&self, distance: isize) -> isize {
+        (self.0)(distance)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Alignment {
+    position: isize,
+    fuel: isize,
+}
+
+fn align(positions: &[isize], cost: &impl Cost) -> Alignment {
+    let mut positions = positions.to_vec();
     positions.sort_unstable();
+    let fuel = |to: isize| -> isize { positions.iter().map(|p| cost.cost((p - to).abs())).sum() };
 
-    let low = positions[0];
-    let high = positions[positions.len() - 1];
-    let mut min_fuel = isize::MAX;
-    for to in low..=high {
-        let om = min_fuel;
-        min_fuel = min_fuel.min(positions.iter().map(|from| fuel_2(*from, to)).sum());
-        if min_fuel < om {
-            // dbg! {(positions[idx], min_fuel)};
+    let (mut lo, mut hi) = cost.bounds(&positions);
+    lo = lo.max(positions[0]);
+    hi = hi.min(positions[positions.len() - 1]).max(lo);
+    if cost.convex() {
+        // Ternary search, convexity means the minimum is never on the
+        // higher side of two probes
+        while hi - lo > 2 {
+            let m1 = lo + (hi - lo) / 3;
+            let m2 = hi - (hi - lo) / 3;
+            match fuel(m1).cmp(&fuel(m2)) {
+                std::cmp::Ordering::Less => hi = m2 - 1,
+                std::cmp::Ordering::Greater => lo = m1 + 1,
+                std::cmp::Ordering::Equal => (lo, hi) = (m1, m2),
+            }
         }
     }
-    Ok(min_fuel)
+    (lo..=hi)
+        .map(|position| Alignment {
+            position,
+            fuel: fuel(position),
+        })
+        .min_by_key(|a| (a.fuel, a.position))
+        .expect("at least one position")
+}
+
+pub fn run_1(input: &str) -> anyhow::Result<isize> {
+    Ok(align(&parse(input)?, &Linear).fuel)
 }
 
-// 100757428 too high
+pub fn run_2(input: &str) -> anyhow::Result<isize> {
+    Ok(align(&parse(input)?, &Triangular).fuel)
+}
 
 fn parse(i: &str) -> anyhow::Result<Vec<isize>> {
     let (_, res) =
@@ -68,10 +176,80 @@ mod tests {
 
     #[test]
     fn aoc7_run_2() {
-        assert_eq!(super::fuel_2(16, 5), 66);
-        assert_eq!(super::fuel_2(1, 5), 10);
-        assert_eq!(super::fuel_2(2, 5), 6);
-        assert_eq!(super::fuel_2(14, 5), 45);
+        use super::Cost;
+        assert_eq!(super::Triangular.cost(11), 66);
+        assert_eq!(super::Triangular.cost(4), 10);
+        assert_eq!(super::Triangular.cost(3), 6);
+        assert_eq!(super::Triangular.cost(9), 45);
         assert_eq!(super::run_2(INPUT).unwrap(), 168);
     }
+
+    #[test]
+    fn aoc7_align() {
+        use super::{align, Alignment, Cost, Linear, Quadratic, Triangular};
+        let positions = super::parse(INPUT).unwrap();
+        assert_eq!(
+            align(&positions, &Linear),
+            Alignment {
+                position: 2,
+                fuel: 37
+            }
+        );
+        assert_eq!(
+            align(&positions, &Triangular),
+            Alignment {
+                position: 5,
+                fuel: 168
+            }
+        );
+
+        // The bounded searches agree with trying every position
+        let scan = |cost: &dyn Cost| {
+            (0..=16)
+                .map(|to| positions.iter().map(|p| cost.cost((p - to).abs())).sum())
+                .min()
+                .unwrap()
+        };
+        assert_eq!(align(&positions, &Quadratic).fuel, scan(&Quadratic));
+        let skewed = [0, 0, 0, 0, 1, 100];
+        assert_eq!(align(&skewed, &Triangular).position, 16);
+
+        // Not convex, cheap to move far away
+        let odd = |d: isize| if d > 10 { 1 } else { d };
+        assert_eq!(
+            align(&positions, &odd),
+            Alignment {
+                position: 2,
+                fuel: 13
+            }
+        );
+        assert_eq!(align(&[7], &Quadratic).fuel, 0);
+    }
+
+    #[test]
+    fn aoc7_ternary() {
+        use super::{align, Convex, Cost};
+        // Spread out crabs, so the search narrows a wide range
+        let positions = (0..200)
+            .map(|i| (i * i * 37) % 1999)
+            .collect::<Vec<isize>>();
+        let scan = |cost: &dyn Cost| {
+            (0..2000)
+                .map(|to| {
+                    positions
+                        .iter()
+                        .map(|p| cost.cost((p - to).abs()))
+                        .sum::<isize>()
+                })
+                .min()
+                .unwrap()
+        };
+        let cubic = Convex(|d: isize| d * d * d);
+        assert_eq!(align(&positions, &cubic).fuel, scan(&cubic));
+        // Flat stretches make the probes compare equal
+        let flat = Convex(|d: isize| (d - 500).max(0));
+        assert_eq!(align(&positions, &flat).fuel, scan(&flat));
+        let linear = Convex(|d: isize| d);
+        assert_eq!(align(&positions, &linear).fuel, scan(&linear));
+    }
 }
@@ -1,133 +1,277 @@
-use std::cell::RefCell;
-use std::{
-    collections::{HashMap, HashSet},
-    fs,
-};
+use std::fs;
 
-pub fn run() -> anyhow::Result<()> {
+pub fn run(args: &[String]) -> anyhow::Result<()> {
     let input = fs::read_to_string("day8.txt")?;
-    println!("day8-1: {}", run_1(&input)?);
-    println!("day8-2: {}", run_2(&input)?);
+    if args.is_empty() {
+        println!("day8-1: {}", run_1(&input)?);
+        println!("day8-2: {}", run_2(&input)?);
+        return Ok(());
+    }
+
+    // `8 --report` shows the wiring and output digits of every entry and
+    // `--display abc,a,...` decodes for a display with those digits, from 0
+    // up, instead of seven segments
+    let mut display = Display::seven_segment();
+    let mut show_report = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--report" => show_report = true,
+            "--display" => {
+                let digits = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--display needs the digits"))?;
+                let digits = digits.split(',').collect::<Vec<_>>();
+                let segments = digits
+                    .iter()
+                    .flat_map(|d| d.chars())
+                    .map(|c| (c as usize).wrapping_sub('a' as usize) + 1)
+                    .max()
+                    .unwrap_or(0);
+                display = Display::new(segments, &digits)?;
+            }
+            _ => return Err(anyhow::anyhow!("Unknown option {}", arg)),
+        }
+    }
+    let io = parse(&input, display.segments)?;
+    if show_report {
+        for (i, o) in io.iter() {
+            println!("{}", report(&display, i, o));
+        }
+    }
+    let sum = io
+        .iter()
+        .map(|(i, o)| analyze(&display, i, o))
+        .sum::<anyhow::Result<usize>>()?;
+    println!("day8: {}", sum);
     Ok(())
 }
 
 fn run_1(input: &str) -> anyhow::Result<usize> {
-    let io = parse(input)?;
+    let io = parse(input, 7)?;
 
-    let outputs: Vec<&Signals> = io.iter().flat_map(|(_, o)| o).collect();
-
-    Ok(outputs
+    Ok(io
         .iter()
-        .filter(|i| [2usize, 3, 4, 7].contains(&i.len()))
+        .flat_map(|(_, o)| o)
+        .filter(|o| [2, 3, 4, 7].contains(&o.count_ones()))
         .count())
 }
 
-fn analyze(mut input: SignalPatterns, output: &[Signals]) -> usize {
-    let true_signals: HashMap<usize, HashSet<char>> = [
-        (0, ['a', 'b', 'c', 'e', 'f', 'g'].iter().copied().collect()),
-        (1, ['c', 'f'].iter().copied().collect()),
-        (2, ['a', 'c', 'd', 'e', 'g'].iter().copied().collect()),
-        (3, ['a', 'c', 'd', 'f', 'g'].iter().copied().collect()),
-        (4, ['b', 'c', 'd', 'f'].iter().copied().collect()),
-        (5, ['a', 'b', 'd', 'f', 'g'].iter().copied().collect()),
-        (6, ['a', 'b', 'd', 'e', 'f', 'g'].iter().copied().collect()),
-        (7, ['a', 'c', 'f'].iter().copied().collect()),
-        (
-            8,
-            ['a', 'b', 'c', 'd', 'e', 'f', 'g']
-                .iter()
-                .copied()
-                .collect(),
-        ),
-        (9, ['a', 'b', 'c', 'd', 'f', 'g'].iter().copied().collect()),
-    ]
-    .iter()
-    .cloned()
-    .collect();
-
-    let mut signals_in_common = HashMap::new();
-    for a in 0..9 {
-        for b in (a + 1)..=9 {
-            let a_s = true_signals.get(&a).unwrap();
-            let b_s = true_signals.get(&b).unwrap();
-            signals_in_common.insert((a, b), a_s.intersection(b_s).count());
+// Segments are bits, segment a is bit 0. `digits[n]` lights up digit n.
+#[derive(Debug, Clone)]
+struct Display {
+    segments: usize,
+    digits: Vec<Signals>,
+}
+
+impl Display {
+    fn new(segments: usize, digits: &[&str]) -> anyhow::Result<Self> {
+        if segments > Signals::BITS as usize {
+            return Err(anyhow::anyhow!(
+                "At most {} segments are supported",
+                Signals::BITS
+            ));
+        }
+        let digits = digits
+            .iter()
+            .map(|d| signals(d, segments))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self { segments, digits })
+    }
+
+    fn seven_segment() -> Self {
+        Self::new(
+            7,
+            &[
+                "abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg",
+                "abcdfg",
+            ],
+        )
+        .expect("valid digits")
+    }
+
+    fn digit(&self, segments: Signals) -> Option<usize> {
+        self.digits.iter().position(|d| *d == segments)
+    }
+}
+
+fn signals(s: &str, segments: usize) -> anyhow::Result<Signals> {
+    s.chars().try_fold(0, |mask, c| {
+        let bit = (c as usize).wrapping_sub('a' as usize);
+        if bit >= segments {
+            return Err(anyhow::anyhow!("Invalid segment {:?}", c));
         }
+        Ok(mask | 1 << bit)
+    })
+}
+
+// Wire n of the observed patterns drives segment `wiring[n]`
+type Wiring = Vec<usize>;
+
+fn rewire(wiring: &[usize], pattern: Signals) -> Signals {
+    wiring
+        .iter()
+        .enumerate()
+        .filter(|(wire, _)| pattern & 1 << wire != 0)
+        .fold(0, |mask, (_, seg)| mask | 1 << seg)
+}
+
+// Finds the wiring that turns every pattern into a digit of the display by
+// trying wires one at a time. After each choice every pattern must still
+// fit a digit of the same size that agrees on the segments chosen so far.
+fn solve(display: &Display, patterns: &[Signals]) -> anyhow::Result<Wiring> {
+    fn fits(display: &Display, patterns: &[Signals], wiring: &[usize]) -> bool {
+        let wired = (0..wiring.len()).fold(0, |mask, wire| mask | 1 << wire);
+        let used = wiring.iter().fold(0, |mask, seg| mask | 1 << seg);
+        patterns.iter().all(|p| {
+            let known = rewire(wiring, p & wired);
+            display
+                .digits
+                .iter()
+                .any(|d| d.count_ones() == p.count_ones() && d & used == known)
+        })
     }
 
-    let mut possibles = HashMap::new();
-    // Find 1, len 2 in input
-    let idx = input.iter().position(|i| i.len() == 2).unwrap();
-    possibles.insert(1, RefCell::new(vec![input.remove(idx)]));
-
-    // Find 4, len 4 in input
-    let idx = input.iter().position(|i| i.len() == 4).unwrap();
-    possibles.insert(4, RefCell::new(vec![input.remove(idx)]));
-
-    // Find 7, len 3 in input
-    let idx = input.iter().position(|i| i.len() == 3).unwrap();
-    possibles.insert(7, RefCell::new(vec![input.remove(idx)]));
-
-    // Find 8, len 7 in input
-    let idx = input.iter().position(|i| i.len() == 7).unwrap();
-    possibles.insert(8, RefCell::new(vec![input.remove(idx)]));
-
-    for i in 0..=9 {
-        possibles
-            .entry(i)
-            .or_insert_with(|| RefCell::new(input.clone()));
-    }
-
-    while possibles.iter().any(|(_k, v)| v.borrow().len() > 1) {
-        for i in 0..9 {
-            for j in (i + 1)..=9 {
-                let mut iv = possibles.get(&i).unwrap().borrow_mut();
-                let mut jv = possibles.get(&j).unwrap().borrow_mut();
-                let in_common = signals_in_common.get(&(i, j)).unwrap();
-                iv.retain(|v1| {
-                    jv.iter()
-                        .any(|v2| v1.intersection(v2).count() == *in_common)
-                });
-                jv.retain(|v1| {
-                    iv.iter()
-                        .any(|v2| v1.intersection(v2).count() == *in_common)
-                });
+    fn search(
+        display: &Display,
+        patterns: &[Signals],
+        wiring: &mut Wiring,
+        found: &mut Vec<Wiring>,
+    ) {
+        if found.len() > 1 {
+            return;
+        }
+        if wiring.len() == display.segments {
+            found.push(wiring.clone());
+            return;
+        }
+        for seg in 0..display.segments {
+            if wiring.contains(&seg) {
+                continue;
+            }
+            wiring.push(seg);
+            if fits(display, patterns, wiring) {
+                search(display, patterns, wiring, found);
             }
+            wiring.pop();
         }
     }
 
-    let mut res = 0;
-    for o in output.iter() {
-        let (k, _) = possibles.iter().find(|(_, v)| &v.borrow()[0] == o).unwrap();
-        res = res * 10 + k;
+    let mut found = Vec::new();
+    search(display, patterns, &mut Vec::new(), &mut found);
+    match found.len() {
+        0 => Err(anyhow::anyhow!("No wiring matches the patterns")),
+        1 => Ok(found.remove(0)),
+        _ => Err(anyhow::anyhow!("More than one wiring matches the patterns")),
     }
+}
+
+fn analyze(display: &Display, input: &[Signals], output: &[Signals]) -> anyhow::Result<usize> {
+    let wiring = solve(display, input)?;
+    output.iter().try_fold(0, |res, o| {
+        let digit = display
+            .digit(rewire(&wiring, *o))
+            .ok_or_else(|| anyhow::anyhow!("Output is not a digit"))?;
+        Ok(res * display.digits.len() + digit)
+    })
+}
+
+// The segments lit by an output as they would show on a seven segment
+// display, three rows of text per digit
+fn render(outputs: &[Signals]) -> String {
+    let lit = |mask: Signals, seg: usize, c: char| if mask & 1 << seg != 0 { c } else { ' ' };
+    let rows: [&dyn Fn(Signals) -> String; 3] = [
+        &|m| format!(" {} ", lit(m, 0, '_')),
+        &|m| format!("{}{}{}", lit(m, 1, '|'), lit(m, 3, '_'), lit(m, 2, '|')),
+        &|m| format!("{}{}{}", lit(m, 4, '|'), lit(m, 6, '_'), lit(m, 5, '|')),
+    ];
+    rows.iter()
+        .map(|row| {
+            outputs
+                .iter()
+                .map(|o| row(*o))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// The output patterns, the solved wiring written as the segment each wire
+// a, b, c... drives, the decoded digits and how they look on the display
+fn report(display: &Display, input: &[Signals], output: &[Signals]) -> String {
+    let letters = |mask: Signals| {
+        (0..display.segments)
+            .filter(|s| mask & 1 << s != 0)
+            .map(|s| (b'a' + s as u8) as char)
+            .collect::<String>()
+    };
+    let mut res = output
+        .iter()
+        .map(|o| letters(*o))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let wiring = match solve(display, input) {
+        Ok(wiring) => wiring,
+        Err(e) => return format!("{}\n{}\n", res, e),
+    };
+
+    let segments = output
+        .iter()
+        .map(|o| rewire(&wiring, *o))
+        .collect::<Vec<_>>();
+    let digits = segments
+        .iter()
+        .map(|s| display.digit(*s).map_or("?".to_string(), |d| d.to_string()))
+        .collect::<String>();
+    res.push_str(&format!(
+        "\nwiring: {} -> {}\ndigits: {}\n{}\n",
+        letters((0..display.segments).fold(0, |mask, s| mask | 1 << s)),
+        wiring
+            .iter()
+            .map(|s| (b'a' + *s as u8) as char)
+            .collect::<String>(),
+        digits,
+        if display.segments == 7 {
+            render(&segments)
+        } else {
+            String::new()
+        }
+    ));
     res
 }
 
 fn run_2(input: &str) -> anyhow::Result<usize> {
-    let io = parse(input)?;
+    let io = parse(input, 7)?;
+    let display = Display::seven_segment();
 
-    Ok(io.into_iter().map(|(i, o)| analyze(i, &o)).sum())
+    io.iter().map(|(i, o)| analyze(&display, i, o)).sum()
 }
 
-fn signal_pattern(i: &str) -> nom::IResult<&str, Signals> {
-    let (i, s) = nom::character::complete::alpha1(i)?;
-
-    let signals = s.chars().collect();
-    Ok((i, signals))
+fn signal_pattern(segments: usize) -> impl Fn(&str) -> nom::IResult<&str, Signals> {
+    move |i| nom::combinator::map_res(nom::character::complete::alpha1, |s| signals(s, segments))(i)
 }
 
-fn input_output(i: &str) -> nom::IResult<&str, (SignalPatterns, OutputValues)> {
-    let signals =
-        |i| nom::multi::separated_list1(nom::bytes::complete::tag(" "), signal_pattern)(i);
-    nom::sequence::separated_pair(signals, nom::bytes::complete::tag(" | "), signals)(i)
+fn input_output(
+    segments: usize,
+) -> impl Fn(&str) -> nom::IResult<&str, (SignalPatterns, OutputValues)> {
+    move |i| {
+        let signals = |i| {
+            nom::multi::separated_list1(nom::bytes::complete::tag(" "), signal_pattern(segments))(i)
+        };
+        nom::sequence::separated_pair(signals, nom::bytes::complete::tag(" | "), signals)(i)
+    }
 }
 
-type Signals = std::collections::HashSet<char>;
+// One bit per wire, wire a is bit 0
+type Signals = u32;
 type SignalPatterns = Vec<Signals>;
 type OutputValues = Vec<Signals>;
-fn parse(i: &str) -> anyhow::Result<Vec<(SignalPatterns, OutputValues)>> {
-    let (_, res) = nom::multi::separated_list1(nom::character::complete::newline, input_output)(i)
-        .map_err(|e| e.to_owned())?;
+// Patterns may use the first `segments` letters
+fn parse(i: &str, segments: usize) -> anyhow::Result<Vec<(SignalPatterns, OutputValues)>> {
+    let (_, res) =
+        nom::multi::separated_list1(nom::character::complete::newline, input_output(segments))(i)
+            .map_err(|e| e.to_owned())?;
 
     Ok(res)
 }
@@ -151,12 +295,126 @@ gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce
 
     #[test]
     fn aoc8_parse() {
-        let rows = super::parse(INPUT).unwrap();
+        let rows = super::parse(INPUT, 7).unwrap();
         let (patterns, output) = &rows[0];
         assert_eq!(patterns.len(), 10);
         assert_eq!(output.len(), 4);
     }
 
+    #[test]
+    fn aoc8_solve() {
+        use super::{solve, Display};
+        let display = Display::seven_segment();
+        let rows = super::parse(INPUT, 7).unwrap();
+        // The puzzle's example wiring, d drives a, e drives b and so on
+        assert_eq!(
+            solve(&display, &rows[0].0).unwrap(),
+            vec![2, 5, 6, 0, 1, 3, 4]
+        );
+
+        // Only 1 and 7 can't tell c from f
+        let few = super::parse("ab dab | ab", 7).unwrap();
+        assert!(solve(&display, &few[0].0).is_err());
+        // A pattern no digit has as many segments
+        assert!(solve(&display, &[0b11111, 0b111111, 0b1]).is_err());
+        assert!(super::parse("abh | ab", 7).is_err());
+    }
+
+    #[test]
+    fn aoc8_custom_display() {
+        use super::{analyze, rewire, solve, Display};
+        // Fourteen segments, a to n, with every digit a different size
+        let display = Display::new(
+            14,
+            &[
+                "a",
+                "ab",
+                "abc",
+                "abcd",
+                "abcde",
+                "abcdef",
+                "abcdefg",
+                "abcdefgh",
+                "abcdefghi",
+                "abcdefghij",
+                "abcdefghijk",
+                "abcdefghijkl",
+                "abcdefghijklm",
+                "abcdefghijklmn",
+            ],
+        )
+        .unwrap();
+        let scramble = vec![3, 11, 0, 13, 6, 1, 9, 12, 2, 10, 4, 8, 5, 7];
+        // Patterns as seen through the scrambled wires
+        let inverse = (0..14)
+            .map(|seg| scramble.iter().position(|s| *s == seg).unwrap())
+            .collect::<Vec<_>>();
+        let patterns = display
+            .digits
+            .iter()
+            .map(|d| rewire(&inverse, *d))
+            .collect::<Vec<_>>();
+        assert_eq!(solve(&display, &patterns).unwrap(), scramble);
+        assert_eq!(
+            analyze(&display, &patterns, &[patterns[1], patterns[13]]).unwrap(),
+            14 + 13
+        );
+
+        // The same entry written out as input
+        let letters = |mask: u32| {
+            (0..14)
+                .filter(|s| mask & 1 << s != 0)
+                .map(|s| (b'a' + s as u8) as char)
+                .collect::<String>()
+        };
+        let line = format!(
+            "{} | {} {}",
+            patterns
+                .iter()
+                .map(|p| letters(*p))
+                .collect::<Vec<_>>()
+                .join(" "),
+            letters(patterns[1]),
+            letters(patterns[13])
+        );
+        let rows = super::parse(&line, 14).unwrap();
+        assert_eq!(rows[0].0, patterns);
+        assert_eq!(analyze(&display, &rows[0].0, &rows[0].1).unwrap(), 14 + 13);
+        assert!(super::parse(&line, 7).is_err());
+    }
+
+    #[test]
+    fn aoc8_display_size() {
+        use super::Display;
+        let all = "abcdefghijklmnopqrstuvwxyzABCDEF";
+        assert!(Display::new(32, &["a"]).is_ok());
+        assert!(Display::new(33, &["a"]).is_err());
+        assert!(Display::new(40, &[all]).is_err());
+    }
+
+    #[test]
+    fn aoc8_report() {
+        let rows = super::parse(INPUT, 7).unwrap();
+        let (i, o) = &rows[0];
+        assert_eq!(
+            super::report(&super::Display::seven_segment(), i, o),
+            "bcdef abcdf bcdef abcdf
+wiring: abcdefg -> cfgabde
+digits: 5353
+ _   _   _   _ 
+|_   _| |_   _|
+ _|  _|  _|  _|
+"
+        );
+
+        let rows = super::parse("ab dab | ab", 7).unwrap();
+        let (i, o) = &rows[0];
+        assert_eq!(
+            super::report(&super::Display::seven_segment(), i, o),
+            "ab\nMore than one wiring matches the patterns\n"
+        );
+    }
+
     #[test]
     fn aoc8_run_1() {
         assert_eq!(super::run_1(INPUT_2).unwrap(), 26);
@@ -1,139 +1,149 @@
 use std::fs;
 
-pub fn run() -> anyhow::Result<()> {
+pub fn run(args: &[String]) -> anyhow::Result<()> {
     let input = fs::read_to_string("day9.txt").unwrap();
+    // `9 --basins` prints the map with every cell labelled by its basin
+    if let Some(flag) = args.first() {
+        if flag != "--basins" {
+            return Err(anyhow::anyhow!("Unknown option {}", flag));
+        }
+        let map = parse(&input)?;
+        println!("{}", label_map(&map, &map.basins()));
+        return Ok(());
+    }
     println!("day9-1: {}", run_1(&input)?);
     println!("day9-2: {}", run_2(&input)?);
     Ok(())
 }
 
-fn run_1(input: &str) -> anyhow::Result<isize> {
-    let map = parse(input)?;
+// Row major heights
+struct HeightMap {
+    width: usize,
+    height: usize,
+    heights: Vec<u8>,
+}
 
-    let get_val = |(row, col): &(isize, isize)| {
-        if *row >= 0
-            && *row < (map.len() as isize)
-            && *col >= 0
-            && *col < (map[*row as usize].len() as isize)
-        {
-            Some(map[*row as usize][*col as usize])
-        } else {
-            None
-        }
-    };
-
-    let mut risk_level = 0;
-    for (r, row) in map.iter().enumerate() {
-        for (c, col) in row.iter().enumerate() {
-            let min_surround = [
-                (r as isize - 1, c as isize),
-                (r as isize + 1, c as isize),
-                (r as isize, c as isize - 1),
-                (r as isize, c as isize + 1),
-            ]
-            .iter()
-            .filter_map(get_val)
-            .min()
-            .unwrap_or(isize::MAX);
-            if *col < min_surround {
-                risk_level += col + 1;
-            }
-        }
-    }
+#[derive(Debug, PartialEq)]
+struct Basin {
+    // The lowest cell, the first one in row major order if there are several
+    low_point: usize,
+    // Row major order
+    cells: Vec<usize>,
+}
 
-    Ok(risk_level)
+impl Basin {
+    fn size(&self) -> usize {
+        self.cells.len()
+    }
 }
 
-fn run_2(input: &str) -> anyhow::Result<usize> {
-    let map = parse(input)?;
+impl HeightMap {
+    fn neighbours(&self, idx: usize) -> impl Iterator<Item = usize> {
+        crate::helper::neighbours(self.width, self.height, idx)
+    }
 
-    let get_val = |(row, col): &(isize, isize)| {
-        if *row >= 0
-            && *row < (map.len() as isize)
-            && *col >= 0
-            && *col < (map[*row as usize].len() as isize)
-        {
-            Some(map[*row as usize][*col as usize])
-        } else {
-            None
-        }
-    };
-
-    let mut basin_points = std::collections::HashSet::new();
-
-    for (r, row) in map.iter().enumerate() {
-        for (c, col) in row.iter().enumerate() {
-            let min_surround = [
-                (r as isize - 1, c as isize),
-                (r as isize + 1, c as isize),
-                (r as isize, c as isize - 1),
-                (r as isize, c as isize + 1),
-            ]
-            .iter()
-            .filter_map(get_val)
-            .min()
-            .unwrap_or(isize::MAX);
-            if *col < min_surround {
-                basin_points.insert((r, c));
-            }
-        }
+    // Cells lower than all of their neighbours
+    fn low_points(&self) -> Vec<usize> {
+        (0..self.heights.len())
+            .filter(|idx| {
+                self.neighbours(*idx)
+                    .all(|n| self.heights[*idx] < self.heights[n])
+            })
+            .collect()
     }
 
-    let is_nbr = |(row, col): &(isize, isize)| {
-        if *row >= 0
-            && *row < (map.len() as isize)
-            && *col >= 0
-            && *col < (map[*row as usize].len() as isize)
-        {
-            map[*row as usize][*col as usize] < 9
-        } else {
-            false
+    // Areas of connected cells below 9, found with union-find so every cell
+    // is only looked at a constant number of times. Ordered by low point.
+    fn basins(&self) -> Vec<Basin> {
+        fn find(parent: &mut [usize], mut idx: usize) -> usize {
+            while parent[idx] != idx {
+                parent[idx] = parent[parent[idx]];
+                idx = parent[idx];
+            }
+            idx
         }
-    };
-
-    let mut basin_sizes: Vec<usize> = Vec::new();
-    while let Some(pt) = basin_points.iter().next().cloned() {
-        let mut searched = std::collections::HashSet::new();
-        let mut to_search = vec![pt];
-        while let Some(pt) = to_search.pop() {
-            searched.insert(pt);
-            basin_points.remove(&pt);
-            let r = pt.0;
-            let c = pt.1;
-            for n in [
-                (r as isize - 1, c as isize),
-                (r as isize + 1, c as isize),
-                (r as isize, c as isize - 1),
-                (r as isize, c as isize + 1),
-            ]
-            .iter()
-            .filter(|c| is_nbr(c))
-            {
-                let n = (n.0 as usize, n.1 as usize);
-                if !searched.contains(&n) {
-                    to_search.push((n.0 as usize, n.1 as usize));
+
+        let mut parent = (0..self.heights.len()).collect::<Vec<_>>();
+        let mut size = vec![1; self.heights.len()];
+        for idx in (0..self.heights.len()).filter(|idx| self.heights[*idx] < 9) {
+            let (row, col) = (idx / self.width, idx % self.width);
+            // Right and down is enough to see every pair of neighbours once
+            let right = (col + 1 < self.width).then(|| idx + 1);
+            let down = (row + 1 < self.height).then(|| idx + self.width);
+            for nbr in [right, down].into_iter().flatten() {
+                if self.heights[nbr] == 9 {
+                    continue;
+                }
+                let (a, b) = (find(&mut parent, idx), find(&mut parent, nbr));
+                if a != b {
+                    let (big, small) = if size[a] >= size[b] { (a, b) } else { (b, a) };
+                    parent[small] = big;
+                    size[big] += size[small];
                 }
             }
         }
 
-        basin_sizes.push(searched.len());
+        let mut by_root: std::collections::HashMap<usize, Basin> = std::collections::HashMap::new();
+        for idx in (0..self.heights.len()).filter(|idx| self.heights[*idx] < 9) {
+            let basin = by_root.entry(find(&mut parent, idx)).or_insert(Basin {
+                low_point: idx,
+                cells: Vec::new(),
+            });
+            if self.heights[idx] < self.heights[basin.low_point] {
+                basin.low_point = idx;
+            }
+            basin.cells.push(idx);
+        }
+        let mut basins = by_root.into_values().collect::<Vec<_>>();
+        basins.sort_by_key(|b| b.low_point);
+        basins
+    }
+}
+
+// Every cell shows the id of its basin, ids go 0-9, a-z and A-Z and then
+// start over. Height 9 cells are shown as #.
+fn label_map(map: &HeightMap, basins: &[Basin]) -> String {
+    const IDS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut res = vec![b'#'; map.heights.len()];
+    for (id, basin) in basins.iter().enumerate() {
+        for c in basin.cells.iter() {
+            res[*c] = IDS[id % IDS.len()];
+        }
     }
+    res.chunks(map.width)
+        .map(|row| String::from_utf8_lossy(row).into_owned())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
+fn run_1(input: &str) -> anyhow::Result<usize> {
+    let map = parse(input)?;
+    Ok(map
+        .low_points()
+        .iter()
+        .map(|idx| map.heights[*idx] as usize + 1)
+        .sum())
+}
+
+fn run_2(input: &str) -> anyhow::Result<usize> {
+    let map = parse(input)?;
+    let mut basin_sizes = map.basins().iter().map(Basin::size).collect::<Vec<_>>();
     basin_sizes.sort_unstable();
     // Multiply the top three sizes
     Ok(basin_sizes.iter().rev().take(3).product())
 }
 
-fn parse(i: &str) -> anyhow::Result<Vec<Vec<isize>>> {
-    let mut res = Vec::new();
-    for line in i.lines() {
-        let mut row: Vec<isize> = Vec::new();
-        for c in line.chars() {
-            row.push(c.to_digit(10).unwrap() as isize);
-        }
-        res.push(row);
-    }
-    Ok(res)
+fn parse(i: &str) -> anyhow::Result<HeightMap> {
+    let (width, height, heights) = crate::helper::grid(i, |c| {
+        c.to_digit(10)
+            .map(|h| h as u8)
+            .ok_or_else(|| anyhow::anyhow!("Invalid height {:?}", c))
+    })?;
+    Ok(HeightMap {
+        width,
+        height,
+        heights,
+    })
 }
 
 #[cfg(test)]
@@ -144,6 +154,42 @@ mod tests {
 8767896789
 9899965678";
 
+    #[test]
+    fn aoc9_parse() {
+        let map = super::parse(INPUT).unwrap();
+        assert_eq!((map.width, map.height), (10, 5));
+        assert!(super::parse("123\n45").is_err());
+        assert!(super::parse("1x3").is_err());
+        assert!(super::parse("").is_err());
+    }
+
+    #[test]
+    fn aoc9_basins() {
+        let map = super::parse(INPUT).unwrap();
+        assert_eq!(map.low_points(), vec![1, 9, 22, 46]);
+
+        let basins = map.basins();
+        assert_eq!(
+            basins.iter().map(|b| b.low_point).collect::<Vec<_>>(),
+            map.low_points()
+        );
+        assert_eq!(
+            basins.iter().map(|b| b.size()).collect::<Vec<_>>(),
+            vec![3, 9, 14, 9]
+        );
+        assert_eq!(basins[0].cells, vec![0, 1, 10]);
+
+        assert_eq!(
+            super::label_map(&map, &basins),
+            "00###11111
+0#222#1#11
+#22222#3#1
+22222#333#
+#2###33333"
+        );
+        assert!(super::parse("12\n3").is_err());
+    }
+
     #[test]
     fn aoc9_run_1() {
         assert_eq!(super::run_1(INPUT).unwrap(), 15);
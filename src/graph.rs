@@ -0,0 +1,387 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+// Anything with nodes numbered 0..node_count and weighted, directed edges
+pub trait Weighted {
+    fn node_count(&self) -> usize;
+    // (target, weight) for every edge leaving `node`
+    fn edges(&self, node: usize) -> impl Iterator<Item = (usize, usize)>;
+    // No edge weighs more than this
+    fn max_weight(&self) -> usize;
+}
+
+// Adjacency lists over nodes named by strings, the names are interned so
+// searches only deal with indices
+#[derive(Debug, Default)]
+pub struct Graph {
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+    adj: Vec<Vec<(usize, usize)>>,
+    max_weight: usize,
+}
+
+impl Graph {
+    pub fn node(&mut self, name: &str) -> usize {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.adj.push(Vec::new());
+        id
+    }
+
+    pub fn id(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: usize) -> &str {
+        &self.names[id]
+    }
+
+    pub fn add_edge(&mut self, from: &str, to: &str, weight: usize) {
+        let (from, to) = (self.node(from), self.node(to));
+        self.adj[from].push((to, weight));
+        self.max_weight = self.max_weight.max(weight);
+    }
+
+    pub fn add_undirected_edge(&mut self, a: &str, b: &str, weight: usize) {
+        self.add_edge(a, b, weight);
+        self.add_edge(b, a, weight);
+    }
+}
+
+impl Weighted for Graph {
+    fn node_count(&self) -> usize {
+        self.names.len()
+    }
+
+    fn edges(&self, node: usize) -> impl Iterator<Item = (usize, usize)> {
+        self.adj[node].iter().copied()
+    }
+
+    fn max_weight(&self) -> usize {
+        self.max_weight
+    }
+}
+
+// Every path from `start` to `end`, found lazily depth first.
+// `allow(node, visits)` decides if `node` may be entered given how many
+// times each node has been visited on the path so far. A path stops the
+// first time it reaches `end`.
+pub fn paths<G, F>(g: &G, start: usize, end: usize, allow: F) -> Paths<'_, G, F>
+where
+    G: Weighted,
+    F: Fn(usize, &[usize]) -> bool,
+{
+    let mut visits = vec![0; g.node_count()];
+    visits[start] = 1;
+    let stack = if start == end {
+        Vec::new()
+    } else {
+        vec![(
+            start,
+            g.edges(start)
+                .map(|(n, _)| n)
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )]
+    };
+    Paths {
+        g,
+        end,
+        allow,
+        stack,
+        visits,
+        trivial: start == end,
+    }
+}
+
+pub struct Paths<'a, G, F> {
+    g: &'a G,
+    end: usize,
+    allow: F,
+    // The current path, each node with the neighbours not yet tried
+    stack: Vec<(usize, std::vec::IntoIter<usize>)>,
+    visits: Vec<usize>,
+    // start == end, the only path is the start node itself
+    trivial: bool,
+}
+
+impl<G, F> Iterator for Paths<'_, G, F>
+where
+    G: Weighted,
+    F: Fn(usize, &[usize]) -> bool,
+{
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.trivial {
+            self.trivial = false;
+            return Some(vec![self.end]);
+        }
+        while let Some((_, nexts)) = self.stack.last_mut() {
+            let Some(next) = nexts.next() else {
+                let (node, _) = self.stack.pop().expect("stack is not empty");
+                self.visits[node] -= 1;
+                continue;
+            };
+            if !(self.allow)(next, &self.visits) {
+                continue;
+            }
+            if next == self.end {
+                let mut path = self.stack.iter().map(|(n, _)| *n).collect::<Vec<_>>();
+                path.push(next);
+                return Some(path);
+            }
+            self.visits[next] += 1;
+            let edges = self.g.edges(next).map(|(n, _)| n).collect::<Vec<_>>();
+            self.stack.push((next, edges.into_iter()));
+        }
+        None
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Path {
+    pub cost: usize,
+    // From start to goal, both included
+    pub nodes: Vec<usize>,
+}
+
+// Nodes waiting to be expanded, popped lowest key first. Keys are cost +
+// heuristic, with a consistent heuristic every key pushed is within two edge
+// weights of the one being expanded, so that many circular buckets are enough.
+// Anything else moves the queue over to a heap.
+enum Frontier {
+    Ring {
+        buckets: Vec<Vec<usize>>,
+        lowest: usize,
+        len: usize,
+    },
+    Heap(BinaryHeap<Reverse<(usize, usize)>>),
+}
+
+// Beyond this many buckets, heavy edges use the heap from the start
+const RING_LIMIT: usize = 1 << 16;
+
+impl Frontier {
+    fn new(max_weight: usize, lowest: usize) -> Self {
+        match max_weight
+            .checked_mul(2)
+            .and_then(|w| w.checked_add(1))
+            .filter(|span| *span <= RING_LIMIT)
+        {
+            Some(span) => Frontier::Ring {
+                buckets: vec![Vec::new(); span],
+                lowest,
+                len: 0,
+            },
+            None => Frontier::Heap(BinaryHeap::new()),
+        }
+    }
+
+    fn push(&mut self, key: usize, node: usize) {
+        match self {
+            Frontier::Ring {
+                buckets,
+                lowest,
+                len,
+            } => {
+                let span = buckets.len();
+                if key >= *lowest && key - *lowest < span {
+                    buckets[key % span].push(node);
+                    *len += 1;
+                    return;
+                }
+                // Bucket b holds the one key in lowest..lowest + span that is
+                // b modulo span
+                let mut heap = BinaryHeap::with_capacity(*len + 1);
+                for (b, bucket) in buckets.iter().enumerate() {
+                    let k = *lowest + (b + span - *lowest % span) % span;
+                    heap.extend(bucket.iter().map(|n| Reverse((k, *n))));
+                }
+                heap.push(Reverse((key, node)));
+                *self = Frontier::Heap(heap);
+            }
+            Frontier::Heap(heap) => heap.push(Reverse((key, node))),
+        }
+    }
+
+    fn pop(&mut self) -> Option<(usize, usize)> {
+        match self {
+            Frontier::Ring {
+                buckets,
+                lowest,
+                len,
+            } => {
+                while *len > 0 {
+                    let span = buckets.len();
+                    if let Some(node) = buckets[*lowest % span].pop() {
+                        *len -= 1;
+                        return Some((*lowest, node));
+                    }
+                    *lowest += 1;
+                }
+                None
+            }
+            Frontier::Heap(heap) => heap.pop().map(|Reverse(e)| e),
+        }
+    }
+}
+
+// A* from `start` to `goal`, with |_| 0 this is plain Dijkstra. The
+// heuristic must never overestimate the remaining cost. If it is also
+// consistent, never dropping by more than the weight of an edge, and no edge
+// weighs more than 32767 the search runs on a bucket queue. Otherwise it
+// falls back to a binary heap and reopens nodes as cheaper paths turn up,
+// which is slower but still finds the cheapest path.
+pub fn astar<G, H>(g: &G, start: usize, goal: usize, heuristic: H) -> Option<Path>
+where
+    G: Weighted,
+    H: Fn(usize) -> usize,
+{
+    let mut dist = vec![usize::MAX; g.node_count()];
+    let mut prev = vec![usize::MAX; g.node_count()];
+
+    dist[start] = 0;
+    let mut frontier = Frontier::new(g.max_weight(), heuristic(start));
+    frontier.push(heuristic(start), start);
+    while let Some((key, node)) = frontier.pop() {
+        if dist[node] + heuristic(node) != key {
+            continue;
+        }
+        if node == goal {
+            break;
+        }
+        for (next, weight) in g.edges(node) {
+            let cost = dist[node] + weight;
+            if cost < dist[next] {
+                dist[next] = cost;
+                prev[next] = node;
+                frontier.push(cost + heuristic(next), next);
+            }
+        }
+    }
+
+    if dist[goal] == usize::MAX {
+        return None;
+    }
+    let mut nodes = vec![goal];
+    while let Some(&p) = nodes
+        .last()
+        .map(|n| &prev[*n])
+        .filter(|p| **p != usize::MAX)
+    {
+        nodes.push(p);
+    }
+    nodes.reverse();
+    Some(Path {
+        cost: dist[goal],
+        nodes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> Graph {
+        let mut g = Graph::default();
+        g.add_undirected_edge("a", "b", 1);
+        g.add_undirected_edge("b", "c", 5);
+        g.add_undirected_edge("a", "d", 2);
+        g.add_undirected_edge("d", "c", 2);
+        g.add_edge("c", "e", 1);
+        g
+    }
+
+    #[test]
+    fn graph_intern() {
+        let mut g = graph();
+        assert_eq!(g.node_count(), 5);
+        assert_eq!(g.max_weight(), 5);
+        assert_eq!(g.id("c"), Some(2));
+        assert_eq!(g.name(2), "c");
+        assert_eq!(g.id("x"), None);
+        assert_eq!(g.node("c"), 2);
+        assert_eq!(g.node_count(), 5);
+    }
+
+    #[test]
+    fn graph_paths() {
+        let g = graph();
+        let (a, c) = (g.id("a").unwrap(), g.id("c").unwrap());
+        let mut res = paths(&g, a, c, |n, visits| visits[n] == 0).collect::<Vec<_>>();
+        res.sort();
+        assert_eq!(res, vec![vec![0, 1, 2], vec![0, 3, 2]]);
+
+        // Allowing every node twice also lets paths bounce back and forth
+        let mut res = paths(&g, a, c, |n, visits| visits[n] < 2);
+        assert!(res.any(|p| p == vec![0, 1, 0, 3, 2]));
+        assert_eq!(
+            paths(&g, c, c, |_, _| true).collect::<Vec<_>>(),
+            vec![vec![2]]
+        );
+    }
+
+    #[test]
+    fn graph_astar() {
+        let g = graph();
+        let (a, e) = (g.id("a").unwrap(), g.id("e").unwrap());
+        let path = astar(&g, a, e, |_| 0).unwrap();
+        assert_eq!(
+            path,
+            Path {
+                cost: 5,
+                nodes: vec![0, 3, 2, 4]
+            }
+        );
+        assert_eq!(astar(&g, e, a, |_| 0), None);
+
+        // Remaining hops times the lightest weight never overestimates
+        let hops = [2, 1, 1, 2, 0];
+        assert_eq!(astar(&g, a, e, |n| hops[n]), astar(&g, a, e, |_| 0));
+    }
+
+    #[test]
+    fn graph_astar_heavy() {
+        // Too heavy for buckets, the heap gives the same path
+        let mut g = Graph::default();
+        let w = 1 << 40;
+        g.add_undirected_edge("a", "b", w);
+        g.add_undirected_edge("b", "c", 5 * w);
+        g.add_undirected_edge("a", "d", 2 * w);
+        g.add_undirected_edge("d", "c", 2 * w);
+        g.add_edge("c", "e", w);
+        let (a, e) = (g.id("a").unwrap(), g.id("e").unwrap());
+        assert_eq!(
+            astar(&g, a, e, |_| 0),
+            Some(Path {
+                cost: 5 * w,
+                nodes: vec![0, 3, 2, 4]
+            })
+        );
+    }
+
+    #[test]
+    fn graph_astar_inconsistent() {
+        // A long row with a shortcut, the heuristic is the true distance on
+        // even nodes and 0 on odd ones so it never overestimates but drops
+        // by more than an edge weight
+        let mut g = Graph::default();
+        let names = (0..12).map(|n| n.to_string()).collect::<Vec<_>>();
+        for n in names.windows(2) {
+            g.add_edge(&n[0], &n[1], 3);
+        }
+        g.add_edge("0", "5", 4);
+        g.add_edge("2", "9", 7);
+        let plain = (0..12)
+            .map(|n| astar(&g, n, 11, |_| 0).unwrap().cost)
+            .collect::<Vec<_>>();
+        let guess = |n: usize| if n.is_multiple_of(2) { plain[n] } else { 0 };
+        assert_eq!(astar(&g, 0, 11, guess), astar(&g, 0, 11, |_| 0));
+        assert_eq!(astar(&g, 0, 11, guess).unwrap().cost, 6 + 7 + 6);
+    }
+}
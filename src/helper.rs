@@ -27,6 +27,45 @@ pub fn uval<U: std::str::FromStr>(i: &str) -> IResult<&str, U> {
     map_res(digit1, |s: &str| s.parse::<U>())(i)
 }
 
+// A rectangular grid with one cell per character, as (width, height, cells)
+// with the cells row major. Blank lines are skipped.
+pub fn grid<T>(
+    input: &str,
+    cell: impl Fn(char) -> anyhow::Result<T>,
+) -> anyhow::Result<(usize, usize, Vec<T>)> {
+    let mut cells = Vec::new();
+    let mut width = 0;
+    let mut height = 0;
+    for line in input.lines().filter(|l| !l.is_empty()) {
+        let before = cells.len();
+        for c in line.chars() {
+            cells.push(cell(c)?);
+        }
+        if height > 0 && cells.len() - before != width {
+            return Err(anyhow::anyhow!("Row {} has the wrong width", height));
+        }
+        width = cells.len() - before;
+        height += 1;
+    }
+    if cells.is_empty() {
+        return Err(anyhow::anyhow!("Empty map"));
+    }
+    Ok((width, height, cells))
+}
+
+// The cells above, below, left and right of `idx` in a row major grid
+pub fn neighbours(width: usize, height: usize, idx: usize) -> impl Iterator<Item = usize> {
+    let (row, col) = (idx / width, idx % width);
+    [
+        (row > 0).then(|| idx - width),
+        (row + 1 < height).then(|| idx + width),
+        (col > 0).then(|| idx - 1),
+        (col + 1 < width).then(|| idx + 1),
+    ]
+    .into_iter()
+    .flatten()
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -42,4 +81,22 @@ mod tests {
         use super::*;
         assert_eq!(uval("123"), Ok(("", 123)));
     }
+
+    #[test]
+    fn helper_grid() {
+        use super::*;
+        let digit = |c: char| c.to_digit(10).ok_or_else(|| anyhow::anyhow!("{:?}", c));
+        assert_eq!(grid("12\n34\n", digit).unwrap(), (2, 2, vec![1, 2, 3, 4]));
+        assert!(grid("12\n3", digit).is_err());
+        assert!(grid("12\n3x", digit).is_err());
+        assert!(grid("\n", digit).is_err());
+    }
+
+    #[test]
+    fn helper_neighbours() {
+        use super::*;
+        assert_eq!(neighbours(3, 2, 0).collect::<Vec<_>>(), vec![3, 1]);
+        assert_eq!(neighbours(3, 2, 4).collect::<Vec<_>>(), vec![1, 3, 5]);
+        assert_eq!(neighbours(1, 1, 0).count(), 0);
+    }
 }
@@ -25,6 +25,7 @@ mod aoc6;
 mod aoc7;
 mod aoc8;
 mod aoc9;
+mod graph;
 mod helper;
 
 fn main() -> anyhow::Result<()> {
@@ -32,25 +33,26 @@ fn main() -> anyhow::Result<()> {
     a.next();
 
     let day = a.next().and_then(|s| s.parse::<usize>().ok()).unwrap_or(0);
+    let args = a.collect::<Vec<_>>();
 
     match day {
-        1 => aoc1::run(),
-        2 => aoc2::run(),
+        1 => aoc1::run(&args),
+        2 => aoc2::run(&args),
         3 => aoc3::run(),
-        4 => aoc4::run(),
-        5 => aoc5::run(),
+        4 => aoc4::run(&args),
+        5 => aoc5::run(&args),
         6 => aoc6::run(),
-        7 => aoc7::run(),
-        8 => aoc8::run(),
-        9 => aoc9::run(),
-        10 => aoc10::run(),
-        11 => aoc11::run(),
-        12 => aoc12::run(),
+        7 => aoc7::run(&args),
+        8 => aoc8::run(&args),
+        9 => aoc9::run(&args),
+        10 => aoc10::run(&args),
+        11 => aoc11::run(&args),
+        12 => aoc12::run(&args),
         13 => aoc13::run(),
         14 => aoc14::run(),
-        15 => aoc15::run(),
+        15 => aoc15::run(&args),
         16 => aoc16::run(),
-        17 => aoc17::run(),
+        17 => aoc17::run(&args),
         18 => aoc18::run(),
         // 19 => aoc19::run(),
         20 => aoc20::run(),
@@ -58,7 +60,7 @@ fn main() -> anyhow::Result<()> {
         22 => aoc22::run(),
         // 23 => aoc23::run(),
         // 24 => aoc24::run(),
-        25 => aoc25::run(),
+        25 => aoc25::run(&args),
         _ => Err(anyhow::anyhow!("Not yet implemented")),
     }
 }